@@ -27,26 +27,29 @@ fn main() {
     f.write(
 br###"// GENERATED BY build.rs
 // DO NOT EDIT BY HAND, CHANGES WILL BE LOST.
-use anyhow::format_err;
 "###).unwrap();
     for module in &days {
         write!(f, "pub mod {};\n", module).unwrap();
     }
 
+    let day_nums: Vec<usize> = days.iter()
+                                   .map(|m| m[3..].parse::<usize>().unwrap())
+                                   .collect();
+    write!(f, "\n// Days with a solution, in order.\n\
+               pub const DAYS: &[usize] = &{:?};\n", day_nums).unwrap();
+
     f.write(br###"
-// Execute the solution for a particular day (1..25).
-pub fn solve(day: usize) -> Result<(), anyhow::Error> {
-    let mut solver: Box<dyn AdventSolver> = match day {
+// The solver for a particular day (1..25).
+fn solver(day: usize) -> Result<Box<dyn AdventSolver>, anyhow::Error> {
+    Ok(match day {
 "###).unwrap();
-    for module in &days {
-        let day_num = module[3..].parse::<usize>().unwrap();
-        write!(f, "         {} => Box::new({}::Solver::default()),\n",
+    for (module, day_num) in days.iter().zip(day_nums.iter()) {
+        write!(f, "        {} => Box::new({}::Solver::default()),\n",
                day_num, module).unwrap();
     }
     f.write(
-br###"         _ => { return Err(format_err!("Invalid day number: {}", day)); }
-    };
-    solver.solve()
+br###"        _ => { return Err(format_err!("Invalid day number: {}", day)); }
+    })
 }
 "###).unwrap();
 }
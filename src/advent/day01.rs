@@ -1,4 +1,5 @@
-use crate::advent::AdventSolver;
+use crate::advent::{AdventSolver, Answers};
+use crate::shared::stopwatch::Stopwatch;
 use anyhow::Error;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
@@ -7,7 +8,8 @@ use std::io::{BufRead, BufReader};
 pub struct Solver;
 
 impl AdventSolver for Solver {
-    fn solve(&mut self) -> Result<(), Error> {
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, Error> {
         let module_masses: Vec<u64> =
             BufReader::new(File::open("input/day01.txt")?)
                       .lines()
@@ -15,6 +17,7 @@ impl AdventSolver for Solver {
                       .iter()
                       .map(|s| s.parse::<u64>())
                       .collect::<Result<Vec<u64>, _>>()?;
+        stopwatch.parsed();
 
         // Part 1
         let fuel_for_modules_naive =
@@ -22,7 +25,7 @@ impl AdventSolver for Solver {
                 .fold(0, |sum, &m| {
                     sum+Self::fuel_for_mass_naive(m)
                 });
-        println!("Fuel for all modules (naive): {}", fuel_for_modules_naive);
+        stopwatch.part1_done();
 
         // Part 2
         let fuel_for_modules_accounting_for_fuel_mass =
@@ -30,9 +33,8 @@ impl AdventSolver for Solver {
                          .fold(0, |sum, &m| {
                              sum+Self::fuel_for_mass_accounting_for_fuel_mass(m)
                          });
-        println!("Fuel for all modules (accounting for fuel mass): {}",
-                 fuel_for_modules_accounting_for_fuel_mass);
-        Ok(())
+        Ok(Answers::new(fuel_for_modules_naive,
+                        fuel_for_modules_accounting_for_fuel_mass))
     }
 }
 
@@ -58,7 +60,7 @@ impl Solver {
 
 #[cfg(test)]
 mod tests {
-    use super::Solver;
+    use super::*;
 
     #[test]
     fn test_fuel_for_mass_naive_examples() {
@@ -93,4 +95,10 @@ mod tests {
             Solver::fuel_for_mass_accounting_for_fuel_mass(u64::max_value())
         );
     }
+
+    #[test]
+    fn test_answers() {
+        assert_eq!(Answers::new(3305301, 4955106),
+                   Solver::default().solve(&mut Stopwatch::new()).unwrap());
+    }
 }
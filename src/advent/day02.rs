@@ -1,17 +1,23 @@
-use crate::advent::AdventSolver;
+use crate::advent::{AdventSolver, Answers};
 use crate::shared::intcode::{Program, Simulator};
-use anyhow::Error;
+use crate::shared::intcode::symbolic::{Executor, Outcome};
+use crate::shared::stopwatch::Stopwatch;
+use anyhow::{Error, format_err};
 
 #[derive(Default)]
 pub struct Solver;
 
 impl AdventSolver for Solver {
-    fn solve(&mut self) -> Result<(), Error> {
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, Error> {
         let program = Program::from_path("input/day02.txt")?;
+        stopwatch.parsed();
 
-        Self::run_1202(&program)?;
-        Self::find_inputs(&program, 19690720)?;
-        Ok(())
+        let output = Self::run(&program, 12, 2)?;
+        stopwatch.part1_done();
+        let (noun, verb) = Self::find_inputs(&program, 19690720)?
+                               .ok_or(format_err!("No inputs found"))?;
+        Ok(Answers::new(output, 100 * noun + verb))
     }
 }
 
@@ -28,22 +34,77 @@ impl Solver {
         Ok(sim.peek(0))
     }
 
-    fn run_1202(program: &Program) -> Result<(), Error> {
-        println!("Output for 1202 input: {}",
-                 Self::run(program, 12, 2)?);
-        Ok(())
-    }
-
-    fn find_inputs(program: &Program, output: isize) -> Result<(), Error> {
-        'outer: for input1 in 0..=99 {
+    // The inputs (from 0 to 99) that produce the output, if any do.
+    fn find_inputs(program: &Program, output: isize)
+        -> Result<Option<(isize, isize)>, Error> {
+        if let Some(inputs) = Self::find_inputs_symbolic(program, output)? {
+            return Ok(Some(inputs));
+        }
+        for input1 in 0..=99 {
             for input2 in 0..=99 {
                 if Self::run(program, input1, input2)? == output {
-                    println!("Inputs {} and {} produce output {}.",
-                             input1, input2, output);
-                    break 'outer;
+                    return Ok(Some((input1, input2)));
                 }
             }
         }
-        Ok(())
+        Ok(None)
+    }
+
+    // Run the program once with symbolic inputs and solve the resulting
+    // expression for the output. Returns None if that doesn't work out (the
+    // program branches on an input, or the output isn't linear in them), in
+    // which case brute force it is.
+    fn find_inputs_symbolic(program: &Program,
+                            output: isize) -> Result<Option<(isize, isize)>,
+                                                     Error> {
+        let mut exec = Executor::new(program);
+        exec.symbolize_cell(1, "noun");
+        exec.symbolize_cell(2, "verb");
+        if exec.run()? != Outcome::Halted {
+            return Ok(None);
+        }
+        let solution = exec.peek(0)
+                           .to_linear()
+                           .and_then(|linear| {
+                               linear.solve(output, &[("noun", 0..=99),
+                                                      ("verb", 0..=99)])
+                           });
+        match solution {
+            Some(values) => {
+                let (input1, input2) = (values["noun"], values["verb"]);
+                // Double check with a concrete run
+                if Self::run(program, input1, input2)? == output {
+                    Ok(Some((input1, input2)))
+                } else {
+                    Ok(None)
+                }
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_inputs_symbolic() {
+        let program = Program::from_path("input/day02.txt").unwrap();
+        assert_eq!(Some((69, 79)),
+                   Solver::find_inputs_symbolic(&program, 19690720).unwrap());
+    }
+
+    #[test]
+    fn test_find_inputs_symbolic_needs_concrete() {
+        // Output is noun * verb, which isn't linear.
+        let program = Program::from_string("2,0,0,0,99").unwrap();
+        assert_eq!(None, Solver::find_inputs_symbolic(&program, 6).unwrap());
+    }
+
+    #[test]
+    fn test_answers() {
+        assert_eq!(Answers::new(3931283, 6979),
+                   Solver::default().solve(&mut Stopwatch::new()).unwrap());
     }
 }
@@ -1,5 +1,6 @@
-use crate::advent::AdventSolver;
+use crate::advent::{AdventSolver, Answers};
 use crate::shared::grid::{Dir, Pos};
+use crate::shared::stopwatch::Stopwatch;
 use anyhow::{format_err, Error};
 use std::collections::HashSet;
 use std::fs::File;
@@ -9,35 +10,26 @@ use std::io::{BufRead, BufReader};
 pub struct Solver;
 
 impl AdventSolver for Solver {
-    fn solve(&mut self) -> Result<(), Error> {
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, Error> {
         let wire_paths: Vec<String> =
             BufReader::new(File::open("input/day03.txt")?)
                       .lines()
                       .collect::<Result<Vec<String>, _>>()?;
         let path1 = Solver::parse_path(&wire_paths[0])?;
         let path2 = Solver::parse_path(&wire_paths[1])?;
+        stopwatch.parsed();
 
         // Part 1
-        match Solver::closest_intersection(&path1, &path2) {
-            Some(pos) => {
-                println!("Closest intersection: {:?} (dist: {})",
-                         pos, pos.manhattan_distance(&Pos::origin()));
-            },
-            None => {
-                println!("No intersections found.");
-            }
-        }
+        let closest = Solver::closest_intersection(&path1, &path2)
+                          .ok_or(format_err!("No intersections found."))?;
+        stopwatch.part1_done();
 
         // Part 2
-        match Solver::minimum_steps_to_intersection(&path1, &path2) {
-            Some(distance) => {
-                println!("Minimum steps to an intersection: {}", distance);
-            },
-            None => {
-                println!("No intersections found.");
-            }
-        }
-        Ok(())
+        let steps = Solver::minimum_steps_to_intersection(&path1, &path2)
+                        .ok_or(format_err!("No intersections found."))?;
+
+        Ok(Answers::new(closest.manhattan_distance(&Pos::origin()), steps))
     }
 }
 
@@ -196,4 +188,10 @@ mod tests {
             Solver::minimum_steps_to_intersection(&path1, &path2).unwrap()
         );
     }
+
+    #[test]
+    fn test_answers() {
+        assert_eq!(Answers::new(557, 56410),
+                   Solver::default().solve(&mut Stopwatch::new()).unwrap());
+    }
 }
@@ -1,4 +1,5 @@
-use crate::advent::AdventSolver;
+use crate::advent::{AdventSolver, Answers};
+use crate::shared::stopwatch::Stopwatch;
 use anyhow::Error;
 
 #[derive(Default)]
@@ -9,7 +10,8 @@ const MIN_PASSWORD: u32 = 123257;
 const MAX_PASSWORD: u32 = 647015;
 
 impl AdventSolver for Solver {
-    fn solve(&mut self) -> Result<(), Error> {
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, Error> {
         let p1_filters = [
             Self::digits_ascending,
             Self::two_adjacent_digits_same,
@@ -19,15 +21,14 @@ impl AdventSolver for Solver {
             Box::new(|p| Self::exactly_two_adjacent_digits_same(p, None)),
         ];
 
-        println!("Part 1: Possible passwords: {}",
-                 (MIN_PASSWORD..MAX_PASSWORD).filter(|&p| {
-                     p1_filters.iter().all(|f| f(p))
-                 }).count());
-        println!("Part 2: Possible passwords: {}",
-                 (MIN_PASSWORD..MAX_PASSWORD).filter(|&p| {
-                     p2_filters.iter().all(|f| f(p))
-                 }).count());
-        Ok(())
+        let part1 = (MIN_PASSWORD..MAX_PASSWORD).filter(|&p| {
+            p1_filters.iter().all(|f| f(p))
+        }).count();
+        stopwatch.part1_done();
+        let part2 = (MIN_PASSWORD..MAX_PASSWORD).filter(|&p| {
+            p2_filters.iter().all(|f| f(p))
+        }).count();
+        Ok(Answers::new(part1, part2))
     }
 }
 
@@ -112,4 +113,10 @@ mod tests {
         assert!(!Solver::exactly_two_adjacent_digits_same(123456, None));
         assert!(!Solver::exactly_two_adjacent_digits_same(821952, None));
     }
+
+    #[test]
+    fn test_answers() {
+        assert_eq!(Answers::new(2220, 1515),
+                   Solver::default().solve(&mut Stopwatch::new()).unwrap());
+    }
 }
@@ -1,24 +1,27 @@
-use crate::advent::AdventSolver;
+use crate::advent::{AdventSolver, Answers};
 use crate::shared::intcode::{Program, Simulator};
+use crate::shared::stopwatch::Stopwatch;
 use anyhow::Error;
 
 #[derive(Default)]
 pub struct Solver;
 
 impl AdventSolver for Solver {
-    fn solve(&mut self) -> Result<(), Error> {
-        println!("Diagnostic test of system 1: {}",
-                 Self::run_diagnostic_test(1)?);
-        println!("Diagnostic test of system 5: {}",
-                 Self::run_diagnostic_test(5)?);
-        Ok(())
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, Error> {
+        let program = Program::from_path("input/day05.txt")?;
+        stopwatch.parsed();
+        // Diagnostic tests of systems 1 and 5
+        let part1 = Self::run_diagnostic_test(&program, 1)?;
+        stopwatch.part1_done();
+        Ok(Answers::new(part1, Self::run_diagnostic_test(&program, 5)?))
     }
 }
 
 impl Solver {
-    fn run_diagnostic_test(input: isize) -> Result<isize, Error> {
-        let program = Program::from_path("input/day05.txt")?;
-        let mut sim = Simulator::with_program(&program);
+    fn run_diagnostic_test(program: &Program,
+                           input: isize) -> Result<isize, Error> {
+        let mut sim = Simulator::with_program(program);
         let input_sender = sim.create_input_channel();
         let output_receiver = sim.create_output_channel();
         input_sender.send(input)?;
@@ -30,3 +33,14 @@ impl Solver {
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_answers() {
+        assert_eq!(Answers::new(7839346, 447803),
+                   Solver::default().solve(&mut Stopwatch::new()).unwrap());
+    }
+}
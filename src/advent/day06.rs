@@ -1,4 +1,5 @@
-use crate::advent::AdventSolver;
+use crate::advent::{AdventSolver, Answers};
+use crate::shared::stopwatch::Stopwatch;
 use anyhow::{format_err, Error};
 use regex::Regex;
 use std::collections::HashMap;
@@ -11,11 +12,14 @@ pub struct Solver;
 type OrbitMap = HashMap<String, String>;
 
 impl AdventSolver for Solver {
-    fn solve(&mut self) -> Result<(), Error> {
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, Error> {
         let orbits = Self::read_orbits_from_file("input/day06.txt")?;
-        println!("Total orbits: {}", Self::count_orbits(&orbits));
-        println!("Transfers to Santa: {}", Self::count_transfers(&orbits));
-        Ok(())
+        stopwatch.parsed();
+        // Total orbits, and transfers to Santa
+        let total = Self::count_orbits(&orbits);
+        stopwatch.part1_done();
+        Ok(Answers::new(total, Self::count_transfers(&orbits)))
     }
 }
 
@@ -108,4 +112,10 @@ mod tests {
         let orbits = Solver::read_orbits_from_lines(&lines).unwrap();
         assert_eq!(4, Solver::count_transfers(&orbits));
     }
+
+    #[test]
+    fn test_answers() {
+        assert_eq!(Answers::new(135690, 298),
+                   Solver::default().solve(&mut Stopwatch::new()).unwrap());
+    }
 }
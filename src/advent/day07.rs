@@ -1,5 +1,6 @@
-use crate::advent::AdventSolver;
+use crate::advent::{AdventSolver, Answers};
 use crate::shared::intcode::{Program, Simulator};
+use crate::shared::stopwatch::Stopwatch;
 use anyhow::Error;
 use permutohedron::LexicalPermutation;
 use std::sync::mpsc::channel;
@@ -10,8 +11,10 @@ pub struct Solver;
 const NUM_AMPLIFIERS: isize = 5;
 
 impl AdventSolver for Solver {
-    fn solve(&mut self) -> Result<(), Error> {
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, Error> {
         let program = Program::from_path("input/day07.txt")?;
+        stopwatch.parsed();
         let mut phases: Vec<isize> = (0..NUM_AMPLIFIERS).collect();
         let mut max_signal: isize = 0;
         loop {
@@ -23,7 +26,9 @@ impl AdventSolver for Solver {
                 break;
             }
         }
-        println!("Max signal from serial config: {}", max_signal);
+        // Max signal from the serial config
+        let serial = max_signal;
+        stopwatch.part1_done();
 
         max_signal = 0;
         phases = (NUM_AMPLIFIERS..NUM_AMPLIFIERS*2).collect();
@@ -36,9 +41,8 @@ impl AdventSolver for Solver {
                 break;
             }
         }
-        println!("Max signal from feedback loop: {}", max_signal);
 
-        Ok(())
+        Ok(Answers::new(serial, max_signal))
     }
 }
 
@@ -108,3 +112,14 @@ impl Solver {
         Ok(output_receiver.recv()?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_answers() {
+        assert_eq!(Answers::new(21000, 61379886),
+                   Solver::default().solve(&mut Stopwatch::new()).unwrap());
+    }
+}
@@ -1,6 +1,6 @@
-use crate::advent::AdventSolver;
+use crate::advent::{AdventSolver, Answers};
+use crate::shared::stopwatch::Stopwatch;
 use anyhow::{Error, format_err};
-use std::fmt;
 use std::fs::File;
 use std::io::Read;
 use std::ops;
@@ -27,28 +27,28 @@ struct Image {
 }
 
 impl AdventSolver for Solver {
-    fn solve(&mut self) -> Result<(), Error> {
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, Error> {
         let mut image_data = String::new();
         File::open("input/day08.txt")?
              .read_to_string(&mut image_data)?;
 
-        // Part 1: Find layer with fewest 0 digits
         let image = Image::from_string(WIDTH, HEIGHT, image_data.trim())?;
+        stopwatch.parsed();
+
+        // Part 1: Find layer with fewest 0 digits
         let layer = image.layers
                          .iter()
                          .enumerate()
                          .map(|(i, l)| (i, l.count_pixels(0)))
                          .min_by_key(|&(_i, count)| count)
                          .unwrap().0;
-        println!("Layer {} has fewest 0 pixels. Checksum: {}",
-                 layer,
-                 image.layers[layer].count_pixels(1) *
-                 image.layers[layer].count_pixels(2));
+        let checksum = image.layers[layer].count_pixels(1) *
+                       image.layers[layer].count_pixels(2);
+        stopwatch.part1_done();
 
         // Part 2: Render image
-        print!("\n{}", image);
-
-        Ok(())
+        Ok(Answers::new(checksum, image.pixels()))
     }
 }
 
@@ -84,6 +84,14 @@ impl Image {
         Ok(image)
     }
 
+    // The visible image, true where white.
+    fn pixels(&self) -> Vec<Vec<bool>> {
+        let flat = self.flatten();
+        flat.pixels.chunks(flat.width)
+                   .map(|row| row.iter().map(|&p| p == 1).collect())
+                   .collect()
+    }
+
     fn flatten(&self) -> ImageLayer {
         let init = ImageLayer::transparent(self.width, self.height);
         self.layers.iter()
@@ -91,24 +99,6 @@ impl Image {
     }
 }
 
-impl fmt::Display for Image {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let flat = self.flatten();
-        for y in 0..flat.height {
-            for x in 0..flat.width {
-                let c = match flat.pixels[y*flat.width+x] {
-                    0|2 => ' ',
-                    1 => '\u{2588}',
-                    _ => unreachable!(),
-                };
-                write!(f, "{}", c)?;
-            }
-            write!(f, "\n")?;
-        }
-        Ok(())
-    }
-}
-
 impl ImageLayer {
     fn empty(width: usize, height: usize) -> Self {
         Self {
@@ -152,3 +142,32 @@ impl ops::Add<&ImageLayer> for ImageLayer {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advent::Answer;
+    use crate::shared::ocr;
+
+    #[test]
+    fn test_message() {
+        // Example from the problem description
+        let image = Image::from_string(2, 2, "0222112222120000").unwrap();
+        assert_eq!(vec![vec![false, true], vec![true, false]],
+                   image.pixels());
+
+        let mut image_data = String::new();
+        File::open("input/day08.txt").unwrap()
+             .read_to_string(&mut image_data).unwrap();
+        let image = Image::from_string(WIDTH, HEIGHT, image_data.trim())
+                          .unwrap();
+        assert_eq!("LJECH", ocr::read_letters(&image.pixels()).unwrap());
+    }
+
+    #[test]
+    fn test_answers() {
+        let answers = Solver::default().solve(&mut Stopwatch::new()).unwrap();
+        assert_eq!(Some(Answer::Number(1792)), answers.part1);
+        assert_eq!("LJECH", answers.part2.unwrap().to_string());
+    }
+}
@@ -1,39 +1,53 @@
-use crate::advent::AdventSolver;
+use crate::advent::{AdventSolver, Answers};
 use crate::shared::intcode::{Program, Simulator};
-use anyhow::Error;
+use crate::shared::stopwatch::Stopwatch;
+use anyhow::{Error, format_err};
 
 #[derive(Default)]
 pub struct Solver;
 
 impl AdventSolver for Solver {
-    fn solve(&mut self) -> Result<(), Error> {
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, Error> {
         let program = Program::from_path("input/day09.txt")?;
+        stopwatch.parsed();
 
-        println!("Part 1, running BOOST test:");
-        Self::run_boost_program(&program, 1)?;
+        // Part 1, running BOOST test
+        let keycode = Self::run_boost_program(&program, 1)?;
+        stopwatch.part1_done();
 
-        println!("Part 2, running sensor boost:");
-        Self::run_boost_program(&program, 2)?;
+        // Part 2, running sensor boost
+        let coordinates = Self::run_boost_program(&program, 2)?;
 
-        Ok(())
+        Ok(Answers::new(keycode, coordinates))
     }
 }
 
 impl Solver {
+    // The program's only output. Anything before it would be opcodes the
+    // BOOST test found to be malfunctioning.
     fn run_boost_program(program: &Program,
-                         input_value: isize) -> Result<(), Error> {
-        let mut sim = Simulator::with_program(program);
+                         input_value: isize) -> Result<isize, Error> {
+        let mut sim = Simulator::with_optimized_program(program);
         let input = sim.create_input_channel();
         let output = sim.create_output_channel();
         input.send(input_value)?;
         sim.run()?;
-        loop {
-            match output.try_recv() {
-                Ok(v) => { println!("Read: {}", v); }
-                Err(_) => { break; },
-            }
+        let values: Vec<isize> = output.try_iter().collect();
+        match values.as_slice() {
+            &[value] => Ok(value),
+            _ => Err(format_err!("Expected one output, got: {:?}", values)),
         }
-        Ok(())
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_answers() {
+        assert_eq!(Answers::new(2351176124isize, 73110),
+                   Solver::default().solve(&mut Stopwatch::new()).unwrap());
+    }
+}
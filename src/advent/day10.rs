@@ -1,14 +1,25 @@
-use crate::advent::AdventSolver;
+use crate::advent::{AdventSolver, Answers};
+use crate::shared::asciicast::RecordingOptions;
+use crate::shared::options::Options;
+use crate::shared::stopwatch::Stopwatch;
 use anyhow::{Error, format_err};
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, Read, Write};
 use std::thread;
 use std::time::Duration;
 
+// Options:
+//   headless    don't show the asteroids being vaporized (cast= and frames=
+//               still record it, without waiting between frames)
+//   cast=PATH   record the vaporization animation to an asciicast file
+//   frames=PATH record the map after each asteroid is vaporized as plain text
 #[derive(Default)]
-pub struct Solver;
+pub struct Solver {
+    headless: bool,
+    recording: RecordingOptions,
+}
 
 #[derive(Copy,Clone,PartialEq)]
 enum Space {
@@ -30,47 +41,89 @@ struct Direction {
 }
 
 impl AdventSolver for Solver {
-    fn solve(&mut self) -> Result<(), Error> {
+    fn configure(&mut self, options: &Options) -> Result<(), Error> {
+        self.headless = options.flag("headless")?;
+        self.recording = RecordingOptions::configure(options)?;
+        Ok(())
+    }
+
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, Error> {
         let mut map_data = String::new();
         File::open("input/day10.txt")?
              .read_to_string(&mut map_data)?;
 
         let map = Solver::read_space_string(&map_data)?;
-        let base = Solver::find_best_station_location(&map);
-        Solver::animate_vaporization(&map, base);
-
-        println!("Best monitoring station location: {:?} (asteroids: {})",
-                 base, Solver::count_asteroids_seen(&map, base));
+        stopwatch.parsed();
 
-        println!("200th asteroid to vaporize: {:?}",
-                 Solver::find_nth_vaporized(&map, base, 200).unwrap());
-        Ok(())
+        // Asteroids seen from the best monitoring station location, and
+        // where the 200th asteroid to be vaporized is
+        let base = Solver::find_best_station_location(&map);
+        let seen = Solver::count_asteroids_seen(&map, base);
+        stopwatch.part1_done();
+
+        let saved = self.animate_vaporization(&map, base)?;
+        let nth = Solver::find_nth_vaporized(&map, base, 200)
+                      .ok_or(format_err!("Fewer than 200 asteroids"))?;
+        let mut answers = Answers::new(seen, nth.x * 100 + nth.y);
+        if !saved.is_empty() {
+            answers.report = Some(saved.join("\n"));
+        }
+        Ok(answers)
     }
 }
 
 impl Solver {
-    fn animate_vaporization(map: &Vec<Vec<Space>>, base: Pos) {
+    // Shown unless headless; recorded if asked to, either way. Returns what
+    // recordings were saved.
+    fn animate_vaporization(&self, map: &Vec<Vec<Space>>, base: Pos)
+        -> Result<Vec<String>, Error> {
+        let mut recorder = self.recording.recorder();
+        let live = !self.headless;
+        if !live && recorder.is_none() {
+            return Ok(Vec::new());
+        }
         let mut map = map.clone();
         let order = Self::vaporization_order(&map, base);
-        print!("\x1B[2J\x1B[?25l"); // Clear screen, hide cursor
-        Self::display_map(&map);
+        let delay = Duration::from_millis(20);
+        let mut show = |text: &str, pause: bool| {
+            if live {
+                print!("{}", text);
+                io::stdout().flush().unwrap_or(());
+            }
+            if let Some(ref mut recorder) = recorder {
+                recorder.write(text);
+                if pause {
+                    recorder.advance(delay);
+                }
+            }
+            if pause && live {
+                thread::sleep(delay);
+            }
+        };
+        show("\x1B[2J\x1B[?25l", false); // Clear screen, hide cursor
+        show(&Self::display_map(&map), true);
         for asteroid in order {
-            thread::sleep(Duration::from_millis(20));
             map[asteroid.y][asteroid.x] = Space::Empty;
-            Self::display_map(&map);
+            show(&Self::display_map(&map), true);
+        }
+        show("\x1B[?25h", false); // Show cursor
+        match recorder {
+            Some(mut recorder) => self.recording.save(&mut recorder),
+            None => Ok(Vec::new()),
         }
-        print!("\x1B[?25h"); // Show cursor
     }
 
-    fn display_map(map: &Vec<Vec<Space>>) {
-        print!("\x1B[H"); // move cursor to top-left
+    fn display_map(map: &Vec<Vec<Space>>) -> String {
+        let mut frame = String::from("\x1B[H"); // move cursor to top-left
         for row in map.iter() {
             for &col in row.iter() {
-                print!("{}", if col == Space::Empty { '.' } else { '#' });
+                frame.push(if col == Space::Empty { '.' } else { '#' });
             }
-            println!("");
+            frame.push('\n');
         }
-        println!("");
+        frame.push('\n');
+        frame
     }
     fn read_space_string(text: &str) -> Result<Vec<Vec<Space>>, Error> {
         let lines = text.split("\n")
@@ -379,4 +432,35 @@ mod tests {
         assert_eq!(Some(Pos {x: 11, y: 1}),
                    Solver::find_nth_vaporized(&map, base, 299));
     }
+
+    #[test]
+    fn test_answers() {
+        let mut solver = Solver::default();
+        solver.configure(&Options::parse(&["headless".to_string()]).unwrap())
+              .unwrap();
+        assert_eq!(Answers::new(296, 204),
+                   solver.solve(&mut Stopwatch::new()).unwrap());
+    }
+
+    #[test]
+    fn test_headless_recording() {
+        let map = Solver::read_space_string(".#..#\n.....\n#####\n....#\n\
+                                             ...##").unwrap();
+        let path = std::env::temp_dir()
+            .join(format!("advent2019_day10_{}.frames", std::process::id()));
+        let path = path.to_str().unwrap();
+        let args = vec!["headless".to_string(), format!("frames={}", path)];
+        let mut solver = Solver::default();
+        solver.configure(&Options::parse(&args).unwrap()).unwrap();
+
+        // Recorded at the usual pace, but not in real time
+        let start = std::time::Instant::now();
+        let saved = solver.animate_vaporization(&map, Pos {x: 3, y: 4})
+                          .unwrap();
+        assert!(start.elapsed() < Duration::from_millis(9 * 20));
+        assert_eq!(vec![format!("Saved frames to {}", path)], saved);
+        let frames = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(frames.contains("--- frame 10 at 0.180s\n"), "{}", frames);
+    }
 }
@@ -1,84 +1,122 @@
-use crate::advent::AdventSolver;
-use crate::shared::grid::{Dir, InfiniteGrid, Pos};
-use crate::shared::intcode::{Program, Simulator};
+use crate::advent::{AdventSolver, Answers};
+use crate::shared::bitmap;
+use crate::shared::intcode::Program;
+use crate::shared::options::Options;
+use crate::shared::painter::{self, Color};
+use crate::shared::stopwatch::Stopwatch;
 use anyhow::{Error, format_err};
-use std::collections::HashSet;
 
+// Options (for the registration identifier painted in part 2):
+//   pbm=PATH    save it as a PBM image
+//   ppm=PATH    save it as a PPM image, in the colors it's painted in
+//   ascii=PATH  save it as ASCII art
+//   scale=N     pixels per panel in the images (default 8)
 #[derive(Default)]
-pub struct Solver;
-
-#[derive(Copy,Clone,PartialEq)]
-enum Color {
-    Black = 0,
-    White = 1,
+pub struct Solver {
+    pbm: Option<String>,
+    ppm: Option<String>,
+    ascii: Option<String>,
+    scale: usize,
 }
 
 impl AdventSolver for Solver {
-    fn solve(&mut self) -> Result<(), Error> {
+    fn configure(&mut self, options: &Options) -> Result<(), Error> {
+        self.pbm = options.get_as("pbm")?;
+        self.ppm = options.get_as("ppm")?;
+        self.ascii = options.get_as("ascii")?;
+        self.scale = options.get_or("scale", 8)?;
+        if self.scale == 0 {
+            return Err(format_err!("scale must be at least 1"));
+        }
+        Ok(())
+    }
+
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, Error> {
         let program = Program::from_path("input/day11.txt")?;
+        stopwatch.parsed();
 
         // Part 1
-        Solver::paint_registration(&program, Color::Black)?;
-        // This one is garbage, don't bother displaying it.
+        let painted = painter::paint(&program, Color::Black)?.painted.len();
+        // The painting is garbage, only the number of panels matters.
+        stopwatch.part1_done();
 
         // Part 2
-        let grid = Solver::paint_registration(&program, Color::White)?;
-        Solver::display_grid(&grid);
+        let image = painter::paint(&program, Color::White)?.image();
+        let saved = self.export(&image)?;
 
-        Ok(())
+        let mut answers = Answers::new(painted, image);
+        if !saved.is_empty() {
+            answers.report = Some(saved.join("\n"));
+        }
+        Ok(answers)
     }
 }
 
 impl Solver {
-    fn paint_registration(program: &Program,
-                          start_color: Color) ->
-                              Result<Vec<Vec<Color>>, Error> {
-        let mut grid: InfiniteGrid<Color> = InfiniteGrid::new(Color::Black);
-        let mut robot_pos = Pos::origin();
-        let mut robot_dir = Dir::Up;
-        let mut panels_painted: HashSet<Pos> = HashSet::new();
-        let mut sim = Simulator::with_program(program);
-        let input = sim.create_input_channel();
-        let output = sim.create_output_channel();
-        sim.set_blocking_input(false);
+    // Save the image in whichever formats were asked for, and say where.
+    fn export(&self, image: &[Vec<bool>]) -> Result<Vec<String>, Error> {
+        let mut saved = Vec::new();
+        if let Some(ref path) = self.pbm {
+            bitmap::save(path, &bitmap::to_pbm(image, self.scale))?;
+            saved.push(format!("Saved PBM image to {}", path));
+        }
+        if let Some(ref path) = self.ppm {
+            let ppm = bitmap::to_ppm(image, self.scale,
+                                     (255, 255, 255), (0, 0, 0));
+            bitmap::save(path, &ppm)?;
+            saved.push(format!("Saved PPM image to {}", path));
+        }
+        if let Some(ref path) = self.ascii {
+            bitmap::save(path, &bitmap::to_ascii(image, '#', '.'))?;
+            saved.push(format!("Saved ASCII art to {}", path));
+        }
+        Ok(saved)
+    }
+}
 
-        grid[robot_pos] = start_color;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advent::Answer;
+    use crate::shared::ocr;
 
-        loop {
-            input.send(grid[robot_pos] as isize)?;
-            sim.run()?;
-            if !sim.is_running() {
-                break;
-            }
+    #[test]
+    fn test_answers() {
+        let program = Program::from_path("input/day11.txt").unwrap();
+        let painting = painter::paint(&program, Color::Black).unwrap();
+        assert_eq!(2293, painting.painted.len());
 
-            grid[robot_pos] = match output.recv()? {
-                0 => Color::Black,
-                1 => Color::White,
-                x => return Err(format_err!("Unrecognized color: {}", x)),
-            };
-            panels_painted.insert(robot_pos);
-            match output.recv()? {
-                0 => robot_dir = robot_dir.turn_left(),
-                1 => robot_dir = robot_dir.turn_right(),
-                x => return Err(format_err!("Unrecognized turn: {}", x)),
-            }
-            robot_pos = robot_pos.neighbor(robot_dir);
-        }
+        let painting = painter::paint(&program, Color::White).unwrap();
+        let image = painting.image();
+        assert_eq!(6, image.len());
+        assert!(painting.painted.len() >= image.iter().flatten()
+                                               .filter(|&&p| p).count());
+        assert_eq!("AHLCPRAL", ocr::read_letters(&image).unwrap());
+    }
 
-        println!("Panels painted: {}", panels_painted.len());
-        Ok(grid.crop())
+    #[test]
+    fn test_solve() {
+        let answers = Solver::default().solve(&mut Stopwatch::new()).unwrap();
+        assert_eq!(Some(Answer::Number(2293)), answers.part1);
+        assert_eq!("AHLCPRAL", answers.part2.unwrap().to_string());
+        assert_eq!(None, answers.report);
     }
 
-    fn display_grid(grid: &Vec<Vec<Color>>) {
-        for row in grid {
-            for col in row {
-                print!("{}", match col {
-                    Color::Black => ' ',
-                    Color::White => '\u{2588}',
-                });
-            }
-            println!("");
-        }
-        println!("");
+    #[test]
+    fn test_export_report() {
+        let path = std::env::temp_dir()
+            .join(format!("advent2019_day11_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        let args = vec![format!("ascii={}", path)];
+        let mut solver = Solver::default();
+        solver.configure(&Options::parse(&args).unwrap()).unwrap();
+
+        let answers = solver.solve(&mut Stopwatch::new()).unwrap();
+        let art = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(Some(format!("Saved ASCII art to {}", path)),
+                   answers.report);
+        assert_eq!(6, art.lines().count());
     }
 }
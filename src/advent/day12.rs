@@ -1,4 +1,5 @@
-use crate::advent::AdventSolver;
+use crate::advent::{AdventSolver, Answers};
+use crate::shared::stopwatch::Stopwatch;
 use anyhow::{Error, format_err};
 use lazy_static::lazy_static;
 use num::integer::lcm;
@@ -40,8 +41,12 @@ struct Moon {
 }
 
 impl AdventSolver for Solver {
-    fn solve(&mut self) -> Result<(), Error> {
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, Error> {
         let mut moons = Self::read_moon_data("input/day12.txt")?;
+        stopwatch.parsed();
+        // Both parts are worked out in the same loop
+        let mut energy = None;
         let mut xstates: HashSet<u64> = HashSet::new();
         let mut ystates: HashSet<u64> = HashSet::new();
         let mut zstates: HashSet<u64> = HashSet::new();
@@ -58,17 +63,17 @@ impl AdventSolver for Solver {
             ystates.insert(ystate);
             zstates.insert(zstate);
             if i == 1000 {
-                println!("Total energy after 1000 steps: {}",
-                         moons.iter()
-                              .map(Moon::energy)
-                              .fold(0, |sum, e| sum + e));
+                energy = Some(moons.iter()
+                                   .map(Moon::energy)
+                                   .sum::<i32>());
             }
             Self::step_system(&mut moons);
         }
-        println!("Total cycle length: {}",
-                 lcm(lcm(xstates.len(), ystates.len()), zstates.len()));
+        let energy = energy.ok_or(format_err!("Cycled within 1000 steps"))?;
+        let cycle = lcm(lcm(xstates.len(), ystates.len()), zstates.len());
 
-        Ok(())
+        // Total energy after 1000 steps, and total cycle length
+        Ok(Answers::new(energy, cycle))
     }
 }
 
@@ -185,3 +190,14 @@ impl Moon {
         self.pos.z += self.vel.z;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_answers() {
+        assert_eq!(Answers::new(10845, 551272644867044usize),
+                   Solver::default().solve(&mut Stopwatch::new()).unwrap());
+    }
+}
@@ -1,16 +1,59 @@
-use crate::advent::AdventSolver;
+use crate::advent::{AdventSolver, Answers};
+use crate::shared::asciicast::{Recorder, RecordingOptions};
 use crate::shared::grid::{InfiniteGrid, Pos};
-use crate::shared::intcode::{Program, Simulator};
+use crate::shared::intcode::{Inspector, Program, Receiver, Recording, Sender,
+                             Simulator, Snapshot};
+use crate::shared::options::Options;
+use crate::shared::stopwatch::Stopwatch;
+use crate::shared::terminal::{Key, RawTerminal};
 use anyhow::{Error, format_err};
 use num_derive::FromPrimitive;
 use num_traits::cast::FromPrimitive;
+use std::collections::{HashSet, VecDeque};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::ops::Index;
 use std::thread;
 use std::time;
 
+// Options:
+//   load=PATH   resume the game saved in PATH instead of starting a new one
+//   save=PATH   save the game to PATH when done (or when 's' is pressed)
+//   moves=N     stop after N joystick moves
+//   play        play the game yourself instead of the autopilot
+//   tick=MS     how long each joystick move takes when playing (default 150)
+//   render      show the autopilot playing (always on when playing)
+//   fps=N       screen updates per second when rendering (default 100)
+//   strategy=S  how the autopilot plays: tracker (default) or predictor
+//   compare     play a game with each strategy and compare how they did
+//   cast=PATH   record the screen to an asciicast file (works headless too)
+//   frames=PATH record the screen after every frame as plain text
+//   inspect     show the Intcode VM running the game instead of the screen
+//   every=N     steps between redraws when inspecting (default 500)
+//   record=PATH save the VM's inputs and outputs to PATH, to replay later
+//   replay=PATH make the joystick moves saved in PATH again, and check that
+//               the game goes the same way (start from the same load=, if any)
 #[derive(Default)]
-pub struct Solver;
+pub struct Solver {
+    load: Option<String>,
+    save: Option<String>,
+    moves: Option<usize>,
+    play: bool,
+    tick: time::Duration,
+    render: bool,
+    fps: u32,
+    strategy: String,
+    compare: bool,
+    recording: RecordingOptions,
+    inspect: bool,
+    every: u64,
+    record: Option<String>,
+    replay: Option<String>,
+}
+
+const STRATEGIES: &[&str] = &["tracker", "predictor"];
 
-#[derive(Clone,Copy,FromPrimitive,Eq,PartialEq)]
+#[derive(Clone,Copy,Debug,FromPrimitive,Eq,PartialEq)]
 enum Tile {
     Empty,
     Wall,
@@ -19,103 +62,881 @@ enum Tile {
     Ball,
 }
 
+// What the game has drawn. Besides the tiles it keeps track of where the
+// ball and paddle are, how many blocks are left and which cells changed since
+// the last frame was drawn, so none of that needs a scan of the whole screen.
+struct Screen {
+    tiles: InfiniteGrid<Tile>,
+    ball: Option<Pos>,
+    paddle: Option<Pos>,
+    blocks: usize,
+    dirty: HashSet<Pos>,
+}
+
+// The arcade cabinet: the game program running on a VM, plus the screen and
+// score it has drawn so far. All of it can be saved to a file and loaded back
+// to resume the game from that point, any number of times.
+struct Arcade {
+    sim: Simulator,
+    input: Sender,
+    output: Receiver,
+    screen: Screen,
+    score: isize,
+    // Draws a frame after every update when set, then pauses for
+    // frame_delay. Frames go to the terminal if live and to the recorder if
+    // there is one.
+    renderer: Option<Renderer>,
+    frame_delay: time::Duration,
+    live: bool,
+    recorder: Option<Recorder>,
+    // Runs the VM instead when set, showing what it's doing.
+    inspector: Option<Inspector>,
+}
+
+// Draws the screen in the terminal: in full the first time (or when the
+// screen grows), after that only the cells that changed, each by moving the
+// cursor to it.
+#[derive(Default)]
+struct Renderer {
+    // The screen's bounds when it was last painted in full. Terminal rows
+    // and columns are relative to its top left corner, under the header.
+    bounds: Option<(Pos, Pos)>,
+    // The score and block count shown in the header.
+    header: Option<(isize, usize)>,
+}
+
+// A way of playing the game: picks the joystick position (-1 left, 0
+// neutral, 1 right) for each move from what's on the screen.
+trait Strategy {
+    fn joystick(&mut self, screen: &Screen) -> isize;
+}
+
+// Keeps the paddle under the ball.
+struct Tracker;
+
+// Works out where the ball will come down to the paddle's row, bouncing it
+// off walls and blocks on the way, and heads there ahead of time. While the
+// ball is going up it just follows it, since there's no telling which blocks
+// will have been broken by the time it comes back.
+#[derive(Default)]
+struct Predictor {
+    last_ball: Option<Pos>,
+}
+
+// Makes the moves of a recorded game, whatever is on the screen.
+struct Replayer {
+    moves: VecDeque<isize>,
+}
+
+// How a strategy did in the harness.
+struct Report {
+    strategy: &'static str,
+    score: isize,
+    inputs: usize,
+    steps: u64,
+    blocks_left: usize,
+}
+
 impl AdventSolver for Solver {
-    fn solve(&mut self) -> Result<(), Error> {
-        let program = Program::from_path("input/day13.txt")?;
-        let mut screen: InfiniteGrid<Tile> = InfiniteGrid::new(Tile::Empty);
-        let mut sim = Simulator::with_program(&program);
+    fn configure(&mut self, options: &Options) -> Result<(), Error> {
+        self.load = options.get_as("load")?;
+        self.save = options.get_as("save")?;
+        self.moves = options.get_as("moves")?;
+        self.play = options.flag("play")?;
+        self.tick = time::Duration::from_millis(options.get_or("tick", 150)?);
+        self.render = options.flag("render")? || self.play;
+        self.fps = options.get_or("fps", 100)?;
+        if self.fps == 0 {
+            return Err(format_err!("fps must be at least 1"));
+        }
+        self.strategy = options.get_or("strategy", "tracker".to_string())?;
+        Self::strategy(&self.strategy)?;
+        self.compare = options.flag("compare")?;
+        self.recording = RecordingOptions::configure(options)?;
+        self.inspect = options.flag("inspect")?;
+        self.every = options.get_or("every", 500)?;
+        if self.inspect && self.render {
+            return Err(format_err!("Can't show the game while inspecting"));
+        }
+        self.record = options.get_as("record")?;
+        self.replay = options.get_as("replay")?;
+        if self.replay.is_some() && self.play {
+            return Err(format_err!("Can't play and replay at once"));
+        }
+        Ok(())
+    }
+
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, Error> {
+        if self.compare {
+            let mut table = format!("{:<12}{:>8}{:>8}{:>12}{:>13}",
+                                    "Strategy", "Score", "Inputs", "Steps",
+                                    "Blocks left");
+            for &name in STRATEGIES {
+                let report = self.evaluate(name)?;
+                table += &format!("\n{:<12}{:>8}{:>8}{:>12}{:>13}",
+                                  report.strategy, report.score,
+                                  report.inputs, report.steps,
+                                  report.blocks_left);
+            }
+            return Ok(Answers { report: Some(table), ..Answers::default() });
+        }
+
+        let mut arcade = self.start()?;
+        let replay = match self.replay {
+            Some(ref path) => Some(Recording::from_path(path)?),
+            None => None,
+        };
+        stopwatch.parsed();
+        let terminal = match self.play {
+            true => Some(RawTerminal::enable()?),
+            false => None,
+        };
+
+        if self.inspect {
+            let delay = time::Duration::from_secs(1) / self.fps;
+            arcade.inspector = Some(Inspector::new(self.every, delay));
+        }
+        if self.record.is_some() || replay.is_some() {
+            arcade.sim.start_recording();
+        }
+        arcade.recorder = self.recording.recorder();
+        let drawing = self.render || arcade.recorder.is_some();
+        if drawing {
+            arcade.renderer = Some(Renderer::default());
+            arcade.frame_delay = time::Duration::from_secs(1) / self.fps;
+            arcade.live = self.render;
+        }
+        arcade.update()?;
+        // Only once nothing can fail before finish_display shows it again
+        if drawing {
+            arcade.emit("\x1B[?25l"); // Hide cursor
+        }
+        // Part 1 is the count on the first screen of a new game, which a
+        // loaded game doesn't show
+        let blocks = match self.load {
+            Some(_) => None,
+            None => Some(arcade.block_count()),
+        };
+        stopwatch.part1_done();
+        let result = if let Some(mut terminal) = terminal {
+            arcade.frame_delay = time::Duration::from_millis(0);
+            self.play(&mut arcade, &mut terminal)
+        } else if let Some(ref recording) = replay {
+            let mut replayer = Replayer { moves: recording.inputs().collect() };
+            let moves = replayer.moves.len();
+            arcade.autoplay(&mut replayer, Some(moves))
+        } else {
+            Self::strategy(&self.strategy).and_then(|mut strategy| {
+                arcade.autoplay(strategy.as_mut(), self.moves)
+            })
+        };
+        if drawing {
+            arcade.finish_display();
+        }
+        if let Some(ref mut inspector) = arcade.inspector {
+            inspector.finish();
+        }
+        let moves = result?;
+
+        // Part 2 is the final score, if the game got that far
+        let mut answers = Answers {
+            part1: blocks.map(|b| b.into()),
+            ..Answers::default()
+        };
+        let mut report = Vec::new();
+        if arcade.is_over() {
+            answers.part2 = Some(arcade.score.into());
+        } else {
+            report.push(format!("Score after {} moves: {} ({} blocks left)",
+                                moves, arcade.score, arcade.block_count()));
+        }
+        if let Some(ref path) = self.save {
+            arcade.save(path)?;
+            report.push(format!("Saved game after {} moves to {}",
+                                moves, path));
+        }
+        let session = arcade.sim.stop_recording().unwrap_or_default();
+        if let Some(ref path) = self.record {
+            session.save(path)?;
+            report.push(format!("Saved session of {} moves to {}",
+                                moves, path));
+        }
+        if let (Some(recording), Some(path)) = (replay, &self.replay) {
+            recording.verify(&session)?;
+            report.push(format!("Replayed {} moves from {}, with the same \
+                                 outputs", moves, path));
+        }
+        if let Some(mut recorder) = arcade.recorder.take() {
+            report.extend(self.recording.save(&mut recorder)?);
+        }
+        if !report.is_empty() {
+            answers.report = Some(report.join("\n"));
+        }
+        Ok(answers)
+    }
+}
+
+impl Solver {
+    // Let the player steer with the arrow keys or a/d, moving the joystick
+    // once per tick (it springs back to neutral if no key was pressed). Ends
+    // when the game does or 'q' (or Ctrl-C) is pressed; returns the number
+    // of moves.
+    fn play(&self, arcade: &mut Arcade,
+            terminal: &mut RawTerminal) -> Result<usize, Error> {
+        let mut status = String::from("\u{2190}/a left, \u{2192}/d right, \
+                                       s save, q quit");
+        let mut moves = 0;
+        'game: while !arcade.is_over() && Some(moves) != self.moves {
+            arcade.display_status(&status);
+            arcade.pause(self.tick);
+            let mut position = 0;
+            for key in terminal.keys()? {
+                match key {
+                    Key::Left | Key::Char('a') => position = -1,
+                    Key::Right | Key::Char('d') => position = 1,
+                    Key::Char('q') | Key::Interrupt => break 'game,
+                    Key::Char('s') => status = match self.save {
+                        Some(ref path) => {
+                            arcade.save(path)?;
+                            format!("Saved to {} after {} moves", path, moves)
+                        },
+                        None => "Set save=PATH to save".to_string(),
+                    },
+                    _ => {},
+                }
+            }
+            arcade.joystick(position)?;
+            moves += 1;
+        }
+        if arcade.is_over() {
+            arcade.display_status(&format!("Game over! Final score: {}",
+                                           arcade.score));
+        }
+        Ok(moves)
+    }
+
+    // The saved game to start from, or a new one.
+    fn start(&self) -> Result<Arcade, Error> {
+        match self.load {
+            Some(ref path) => Arcade::load(path),
+            None => Ok(Arcade::new(&Program::from_path("input/day13.txt")?)),
+        }
+    }
+
+    fn strategy(name: &str) -> Result<Box<dyn Strategy>, Error> {
+        match name {
+            "tracker" => Ok(Box::new(Tracker)),
+            "predictor" => Ok(Box::new(Predictor::default())),
+            _ => Err(format_err!("Unknown strategy: {} (try one of: {})",
+                                 name, STRATEGIES.join(", "))),
+        }
+    }
+
+    // Play a game to the end (or for the number of moves given) with the
+    // strategy and see how it went.
+    fn evaluate(&self, name: &'static str) -> Result<Report, Error> {
+        let mut arcade = self.start()?;
+        arcade.update()?;
+        let steps = arcade.sim.steps();
+        let mut strategy = Self::strategy(name)?;
+        let inputs = arcade.autoplay(strategy.as_mut(), self.moves)?;
+        Ok(Report {
+            strategy: name,
+            score: arcade.score,
+            inputs: inputs,
+            steps: arcade.sim.steps() - steps,
+            blocks_left: arcade.block_count(),
+        })
+    }
+}
+
+impl Strategy for Tracker {
+    fn joystick(&mut self, screen: &Screen) -> isize {
+        match (screen.ball, screen.paddle) {
+            (Some(ball), Some(paddle)) => (ball.col - paddle.col).signum(),
+            _ => 0,
+        }
+    }
+}
+
+impl Strategy for Replayer {
+    fn joystick(&mut self, _screen: &Screen) -> isize {
+        self.moves.pop_front().unwrap_or(0)
+    }
+}
+
+impl Strategy for Predictor {
+    fn joystick(&mut self, screen: &Screen) -> isize {
+        let (ball, paddle) = match (screen.ball, screen.paddle) {
+            (Some(ball), Some(paddle)) => (ball, paddle),
+            _ => return 0,
+        };
+        let target = match self.last_ball.replace(ball) {
+            Some(last) if ball.row > last.row => {
+                let velocity = (ball.row - last.row, ball.col - last.col);
+                Self::landing(screen, ball, velocity, paddle.row)
+                    .unwrap_or(ball.col)
+            },
+            _ => ball.col,
+        };
+        (target - paddle.col).signum()
+    }
+}
+
+impl Predictor {
+    // The column the ball will be in when it gets to the row above the
+    // paddle, if it keeps going down until then.
+    fn landing(screen: &Screen, mut ball: Pos,
+               (mut dy, mut dx): (isize, isize),
+               paddle_row: isize) -> Option<isize> {
+        let solid = |row, col| {
+            matches!(screen[Pos::new(row, col)], Tile::Wall | Tile::Block)
+        };
+        while dy > 0 {
+            if ball.row + 1 >= paddle_row {
+                return Some(ball.col);
+            }
+            let side = solid(ball.row, ball.col + dx);
+            let ahead = solid(ball.row + dy, ball.col);
+            if side {
+                dx = -dx;
+            }
+            if ahead {
+                dy = -dy;
+            }
+            if !side && !ahead && solid(ball.row + dy, ball.col + dx) {
+                dx = -dx;
+                dy = -dy;
+            }
+            ball = Pos::new(ball.row + dy, ball.col + dx);
+        }
+        None
+    }
+}
+
+impl Screen {
+    fn new() -> Self {
+        Screen {
+            tiles: InfiniteGrid::new(Tile::Empty),
+            ball: None,
+            paddle: None,
+            blocks: 0,
+            dirty: HashSet::new(),
+        }
+    }
+
+    fn set(&mut self, pos: Pos, tile: Tile) {
+        let old = self.tiles[pos];
+        if old == tile {
+            return;
+        }
+        match old {
+            Tile::Block => self.blocks -= 1,
+            Tile::Ball if self.ball == Some(pos) => self.ball = None,
+            Tile::Paddle if self.paddle == Some(pos) => self.paddle = None,
+            _ => {},
+        }
+        match tile {
+            Tile::Block => self.blocks += 1,
+            Tile::Ball => self.ball = Some(pos),
+            Tile::Paddle => self.paddle = Some(pos),
+            _ => {},
+        }
+        self.tiles[pos] = tile;
+        self.dirty.insert(pos);
+    }
+}
+
+impl Index<Pos> for Screen {
+    type Output = Tile;
+
+    fn index(&self, pos: Pos) -> &Tile {
+        &self.tiles[pos]
+    }
+}
+
+impl Arcade {
+    // A new game, with a quarter inserted.
+    fn new(program: &Program) -> Self {
+        let mut sim = Simulator::with_optimized_program(program);
+        sim.poke(0, 2); // Insert quarter
+        Self::with_simulator(sim)
+    }
+
+    fn with_simulator(mut sim: Simulator) -> Self {
         let input = sim.create_input_channel();
         let output = sim.create_output_channel();
-        let mut score: isize = 0;
+        Arcade {
+            sim: sim,
+            input: input,
+            output: output,
+            screen: Screen::new(),
+            score: 0,
+            renderer: None,
+            frame_delay: time::Duration::from_millis(0),
+            live: false,
+            recorder: None,
+            inspector: None,
+        }
+    }
 
-        print!("\x1B[2J\x1B[?25l"); // Clear screen, hide cursor
-        sim.poke(0, 2); // Insert quarter
+    fn load(path: &str) -> Result<Self, Error> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        Self::from_text(&text)
+    }
+
+    fn save(&mut self, path: &str) -> Result<(), Error> {
+        let text = self.to_text();
+        File::create(path)?.write_all(text.as_bytes())?;
+        Ok(())
+    }
+
+    // A save is the score and screen, one "tiles <row> <col> <tiles>" line
+    // per row of the screen with the tiles as digits, followed by a snapshot
+    // of the VM.
+    fn to_text(&mut self) -> String {
+        let mut text = format!("score {}\n", self.score);
+        if let Some((top_left, _)) = self.screen.tiles.bounds() {
+            for (i, row) in self.screen.tiles.crop().iter().enumerate() {
+                let tiles: String = row.iter()
+                                       .map(|&t| (b'0' + t as u8) as char)
+                                       .collect();
+                text.push_str(&format!("tiles {} {} {}\n",
+                                       top_left.row + i as isize,
+                                       top_left.col, tiles));
+            }
+        }
+        text.push_str(&self.sim.snapshot().to_string());
+        text
+    }
 
-        loop {
-            sim.run()?;
-
-            // Read any buffered output and then render screen
-            while let Ok(x) = output.try_recv() {
-                if x == -1 {
-                    output.recv()?;
-                    score = output.recv()?;
-                } else {
-                    let pos = Pos::new(output.recv()?, x);
-                    let tile = output.recv()?;
-                    match Tile::from_isize(tile) {
-                        Some(tile) => screen[pos] = tile,
-                        None => return Err(format_err!("Bad tile: {}", tile)),
+    fn from_text(text: &str) -> Result<Self, Error> {
+        let mut screen = Screen::new();
+        let mut score = 0;
+        let mut vm = String::new();
+        for (i, line) in text.lines().enumerate() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let error = || format_err!("Bad save on line {}: {}", i+1, line);
+            match fields.first() {
+                Some(&"score") if fields.len() == 2 => {
+                    score = fields[1].parse()?;
+                },
+                Some(&"tiles") if fields.len() == 4 => {
+                    let row: isize = fields[1].parse()?;
+                    let col: isize = fields[2].parse()?;
+                    for (j, c) in fields[3].chars().enumerate() {
+                        let tile = c.to_digit(10)
+                                    .and_then(Tile::from_u32)
+                                    .ok_or_else(error)?;
+                        screen.set(Pos::new(row, col + j as isize), tile);
                     }
-                }
-                Self::display_screen(&screen, score);
+                },
+                Some(&"score") | Some(&"tiles") => return Err(error()),
+                _ => {
+                    vm.push_str(line);
+                    vm.push('\n');
+                },
             }
-            if !sim.is_running() {
-                break;
+        }
+        let sim = Simulator::with_snapshot(&Snapshot::from_string(&vm)?);
+        let mut arcade = Self::with_simulator(sim);
+        arcade.screen = screen;
+        arcade.score = score;
+        Ok(arcade)
+    }
+
+    fn is_over(&self) -> bool {
+        !self.sim.is_running()
+    }
+
+    // Let the strategy play until the game is over or it has made max_moves
+    // moves. Returns the number of moves made.
+    fn autoplay(&mut self, strategy: &mut dyn Strategy,
+                max_moves: Option<usize>) -> Result<usize, Error> {
+        let mut moves = 0;
+        while !self.is_over() && Some(moves) != max_moves {
+            self.joystick(strategy.joystick(&self.screen))?;
+            moves += 1;
+        }
+        Ok(moves)
+    }
+
+    // Tilt the joystick (-1 left, 0 neutral, 1 right) and let the game run
+    // until it reads the joystick again.
+    fn joystick(&mut self, position: isize) -> Result<(), Error> {
+        self.input.send(position)?;
+        self.update()
+    }
+
+    // Run the game until it needs input, drawing whatever it outputs.
+    fn update(&mut self) -> Result<(), Error> {
+        match self.inspector {
+            Some(ref mut inspector) => inspector.run(&mut self.sim)?,
+            None => self.sim.run()?,
+        }
+
+        // Read any buffered output and then render screen
+        while let Ok(x) = self.output.try_recv() {
+            if x == -1 {
+                self.output.recv()?;
+                self.score = self.output.recv()?;
+            } else {
+                let pos = Pos::new(self.output.recv()?, x);
+                let tile = self.output.recv()?;
+                match Tile::from_isize(tile) {
+                    Some(tile) => self.screen.set(pos, tile),
+                    None => return Err(format_err!("Bad tile: {}", tile)),
+                }
             }
+        }
+        if let Some(frame) = self.next_frame() {
+            self.emit(&frame);
+            self.pause(self.frame_delay);
+        }
+        Ok(())
+    }
+
+    // Write to the terminal and/or recording.
+    fn emit(&mut self, text: &str) {
+        if self.live {
+            print!("{}", text);
+            io::stdout().flush().unwrap_or(());
+        }
+        if let Some(ref mut recorder) = self.recorder {
+            recorder.write(text);
+        }
+    }
+
+    fn pause(&mut self, duration: time::Duration) {
+        if self.live {
+            thread::sleep(duration);
+        }
+        if let Some(ref mut recorder) = self.recorder {
+            recorder.advance(duration);
+        }
+    }
+
+    // Used for part 1
+    fn block_count(&self) -> usize {
+        self.screen.blocks
+    }
 
-            // Move paddle toward ball
-            let ball_col = Self::find_tile(&screen, Tile::Ball);
-            let paddle_col = Self::find_tile(&screen, Tile::Paddle);
-            if ball_col.is_some() && paddle_col.is_some() {
-                if ball_col.unwrap() < paddle_col.unwrap() {
-                    input.send(-1)?;
-                } else if ball_col.unwrap() == paddle_col.unwrap() {
-                    input.send(0)?;
-                } else {
-                    input.send(1)?;
+    // What to print to bring the terminal up to date with the screen, if
+    // rendering.
+    fn next_frame(&mut self) -> Option<String> {
+        let renderer = self.renderer.as_mut()?;
+        let screen = &mut self.screen;
+        let (top_left, _) = screen.tiles.bounds()?;
+        let mut frame = String::new();
+        if renderer.bounds != screen.tiles.bounds() {
+            frame.push_str("\x1B[2J"); // Clear screen
+            for (i, row) in screen.tiles.crop().iter().enumerate() {
+                frame.push_str(&format!("\x1B[{};1H", i + 2));
+                for &tile in row {
+                    frame.push_str(Self::glyph(tile));
                 }
             }
+            renderer.bounds = screen.tiles.bounds();
+            renderer.header = None;
+            screen.dirty.clear();
+        }
+        for pos in screen.dirty.drain() {
+            frame.push_str(&format!("\x1B[{};{}H{}",
+                                    pos.row - top_left.row + 2,
+                                    pos.col - top_left.col + 1,
+                                    Self::glyph(screen.tiles[pos])));
         }
-        print!("\x1B[?25h"); // Show cursor
+        let header = (self.score, screen.blocks);
+        if renderer.header != Some(header) {
+            frame.push_str(&format!("\x1B[H\x1B[36;1mScore: {}  Blocks: {}\
+                                     \x1B[K", header.0, header.1));
+            renderer.header = Some(header);
+        }
+        Some(frame)
+    }
 
-        Ok(())
+    fn glyph(tile: Tile) -> &'static str {
+        match tile {
+            Tile::Empty => " ",
+            Tile::Wall => "\x1B[37;1m\u{2588}",
+            Tile::Block => "\x1B[34m\u{2584}",
+            Tile::Paddle => "\x1B[32;1m\u{2501}",
+            Tile::Ball => "\x1B[31;1m\u{2b24}",
+        }
+    }
+
+    // The terminal row just under the screen.
+    fn status_row(&self) -> isize {
+        match self.renderer.as_ref().and_then(|r| r.bounds) {
+            Some((top_left, bottom_right)) => {
+                bottom_right.row - top_left.row + 3
+            },
+            None => 1,
+        }
+    }
+
+    // Show a line of text under the screen.
+    fn display_status(&mut self, text: &str) {
+        let row = self.status_row();
+        self.emit(&format!("\x1B[{};1H\x1B[0m{}\x1B[K", row, text));
+    }
+
+    // Leave the cursor under the screen and status line, and show it again.
+    fn finish_display(&mut self) {
+        let row = self.status_row() + 1;
+        self.emit(&format!("\x1B[{};1H\x1B[0m\x1B[?25h\n", row));
     }
 }
 
-impl Solver {
-    // Used for part 1
-    #[allow(dead_code)]
-    fn block_count(screen: &InfiniteGrid<Tile>) -> usize {
-        let cropped = screen.crop();
-        cropped.iter()
-               .map(|row| row.iter().filter(|&t| t == &Tile::Block).count())
-               .fold(0, |sum, c| sum + c)
-    }
-
-    fn display_screen(screen: &InfiniteGrid<Tile>, score: isize) {
-        // Hack: Only display screen if ball & paddle are present (reduce
-        // flicker when ball erased and speeds up initial screen paint).
-        if Self::find_tile(&screen, Tile::Ball).is_none() ||
-           Self::find_tile(&screen, Tile::Paddle).is_none() {
-            return;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Play a new game for some moves with the tracker.
+    fn mid_game(moves: usize) -> Arcade {
+        let program = Program::from_path("input/day13.txt").unwrap();
+        let mut arcade = Arcade::new(&program);
+        arcade.update().unwrap();
+        arcade.autoplay(&mut Tracker, Some(moves)).unwrap();
+        arcade
+    }
+
+    fn play_to_end(arcade: &mut Arcade) {
+        arcade.autoplay(&mut Tracker, None).unwrap();
+    }
+
+    #[test]
+    fn test_answers() {
+        let mut arcade = mid_game(0);
+        assert_eq!(344, arcade.block_count());
+        let moves = arcade.autoplay(&mut Tracker, None).unwrap();
+        assert!(arcade.is_over());
+        assert!(moves > 1000, "{}", moves);
+        assert_eq!(17336, arcade.score);
+    }
+
+    #[test]
+    fn test_strategies() {
+        let solver = Solver::default();
+        for &name in STRATEGIES {
+            let report = solver.evaluate(name).unwrap();
+            assert_eq!((17336, 0), (report.score, report.blocks_left),
+                       "{}", name);
+            assert!(report.inputs > 1000, "{}", report.inputs);
         }
-        let cropped = screen.crop();
-        print!("\x1B[H"); // move cursor to top left
-        println!("\x1B[36;1mScore: {}", score);
-        for row in cropped {
-            for tile in row {
-                print!("{}", match tile {
-                    Tile::Empty => " ",
-                    Tile::Wall => "\x1B[37;1m\u{2588}",
-                    Tile::Block => "\x1B[34m\u{2584}",
-                    Tile::Paddle => "\x1B[32;1m\u{2501}",
-                    Tile::Ball => "\x1B[31;1m\u{2b24}",
-                });
-            }
-            print!("\n");
+        assert!(Solver::strategy("random").is_err());
+    }
+
+    #[test]
+    fn test_landing() {
+        let mut screen = Screen::new();
+        for row in 0..7 {
+            screen.set(Pos::new(row, 0), Tile::Wall);
+            screen.set(Pos::new(row, 6), Tile::Wall);
         }
-        thread::sleep(time::Duration::from_millis(10));
+        screen.set(Pos::new(6, 2), Tile::Paddle);
+        // Bounces off the right wall on the way down
+        assert_eq!(Some(3), Predictor::landing(&screen, Pos::new(2, 4),
+                                               (1, 1), 6));
+        assert_eq!(None, Predictor::landing(&screen, Pos::new(2, 4),
+                                            (-1, 1), 6));
     }
 
-    fn find_tile(screen: &InfiniteGrid<Tile>, to_find: Tile) -> Option<isize> {
-        let screen = screen.crop();
-        for row in screen {
-            for col in 0..row.len() {
-                if row[col] == to_find {
-                    return Some(col as isize);
+    #[test]
+    fn test_tracking() {
+        let mut arcade = mid_game(0);
+        let scan = |screen: &Screen, to_find: Tile| {
+            let mut found = Vec::new();
+            for (row, tiles) in screen.tiles.crop().iter().enumerate() {
+                for (col, &tile) in tiles.iter().enumerate() {
+                    if tile == to_find {
+                        found.push(Pos::new(row as isize, col as isize));
+                    }
                 }
             }
+            found
+        };
+        for _ in 0..300 {
+            let screen = &arcade.screen;
+            assert_eq!(scan(screen, Tile::Ball),
+                       screen.ball.into_iter().collect::<Vec<Pos>>());
+            assert_eq!(scan(screen, Tile::Paddle),
+                       screen.paddle.into_iter().collect::<Vec<Pos>>());
+            assert_eq!(scan(screen, Tile::Block).len(), screen.blocks);
+            arcade.autoplay(&mut Tracker, Some(1)).unwrap();
         }
-        None
+    }
+
+    #[test]
+    fn test_incremental_frames() {
+        let mut arcade = mid_game(10);
+        arcade.renderer = Some(Renderer::default());
+        let first = arcade.next_frame().unwrap();
+        assert!(first.starts_with("\x1B[2J"));
+        assert!(first.contains(&format!("Score: {}  Blocks: {}",
+                                        arcade.score, arcade.block_count())));
+
+        // Only the ball and paddle move from here. (Playing with a renderer
+        // set would print the frame.)
+        let renderer = arcade.renderer.take();
+        arcade.autoplay(&mut Tracker, Some(1)).unwrap();
+        arcade.renderer = renderer;
+        let frame = arcade.next_frame().unwrap();
+        assert!(!frame.contains("\x1B[2J") && !frame.contains("Score"));
+        let cells = frame.matches('H').count();
+        assert!((2..=4).contains(&cells), "{:?}", frame);
+        assert_eq!("", arcade.next_frame().unwrap());
+    }
+
+    #[test]
+    fn test_inspect() {
+        let program = Program::from_path("input/day13.txt").unwrap();
+        let mut arcade = Arcade::new(&program);
+        arcade.inspector = Some(Inspector::new(10_000,
+                                               time::Duration::from_millis(0)));
+        arcade.update().unwrap();
+        arcade.autoplay(&mut Tracker, Some(100)).unwrap();
+        let plain = mid_game(100);
+        assert_eq!(plain.score, arcade.score);
+        assert_eq!(plain.sim.steps(), arcade.sim.steps());
+        assert!(plain.screen.tiles.crop() == arcade.screen.tiles.crop());
+
+        let options = Options::parse(&["inspect".to_string(),
+                                       "render".to_string()]).unwrap();
+        assert!(Solver::default().configure(&options).is_err());
+    }
+
+    #[test]
+    fn test_save_and_resume() {
+        let mut arcade = mid_game(500);
+        let path = std::env::temp_dir()
+            .join(format!("advent2019_day13_{}.sav", std::process::id()));
+        let path = path.to_str().unwrap();
+        arcade.save(path).unwrap();
+        let mut resumed = Arcade::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(arcade.score, resumed.score);
+        assert!(arcade.screen.tiles.crop() == resumed.screen.tiles.crop());
+        assert!(arcade.score > 0 && arcade.block_count() > 0);
+
+        play_to_end(&mut arcade);
+        play_to_end(&mut resumed);
+        assert_eq!(0, resumed.block_count());
+        assert_eq!(arcade.score, resumed.score);
+        assert_eq!(arcade.sim.steps(), resumed.sim.steps());
+    }
+
+    #[test]
+    fn test_branch_from_save() {
+        let text = mid_game(100).to_text();
+        let mut branches: Vec<Arcade> = (0..3).map(|_| {
+            Arcade::from_text(&text).unwrap()
+        }).collect();
+        // Hold the joystick left, neutral and right for a while
+        for _ in 0..10 {
+            for (branch, position) in branches.iter_mut().zip(-1..=1) {
+                branch.joystick(position).unwrap();
+            }
+        }
+        let paddles: Vec<Option<isize>> = branches.iter()
+            .map(|b| b.screen.paddle.map(|p| p.col))
+            .collect();
+        assert!(paddles[0] < paddles[1] && paddles[1] < paddles[2],
+                "{:?}", paddles);
+
+        // The save itself is unaffected
+        let mut fresh = Arcade::from_text(&text).unwrap();
+        assert_eq!(text, fresh.to_text());
+    }
+
+    #[test]
+    fn test_bad_save() {
+        let error = |text: &str| Arcade::from_text(text).err().unwrap()
+                                                        .to_string();
+        assert_eq!("Bad save on line 2: tiles 0 0 0x",
+                   error("score 10\ntiles 0 0 0x\n"));
+        assert_eq!("Snapshot has no mem",
+                   error("score 10\npc 0\nrb 0\nsteps 0\nstate wait\n"));
+    }
+
+    #[test]
+    fn test_solve() {
+        let mut solver = Solver::default();
+        solver.configure(&Options::default()).unwrap();
+        assert_eq!(Answers::new(344, 17336),
+                   solver.solve(&mut Stopwatch::new()).unwrap());
+    }
+
+    #[test]
+    fn test_solve_loaded() {
+        let path = std::env::temp_dir()
+            .join(format!("advent2019_day13_load_{}.sav", std::process::id()));
+        let path = path.to_str().unwrap();
+        mid_game(500).save(path).unwrap();
+        let mut solver = Solver::default();
+        let load = format!("load={}", path);
+        solver.configure(&Options::parse(&[load]).unwrap()).unwrap();
+        let answers = solver.solve(&mut Stopwatch::new()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(None, answers.part1);
+        assert_eq!(Some(17336.into()), answers.part2);
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let solve = |args: &[String]| {
+            let mut solver = Solver::default();
+            solver.configure(&Options::parse(args).unwrap()).unwrap();
+            solver.solve(&mut Stopwatch::new())
+        };
+        let path = std::env::temp_dir()
+            .join(format!("advent2019_day13_{}.session", std::process::id()));
+        let path = path.to_str().unwrap();
+        let answers = solve(&[format!("record={}", path),
+                              "strategy=predictor".to_string()]).unwrap();
+        assert_eq!((Some(344.into()), Some(17336.into())),
+                   (answers.part1, answers.part2));
+
+        // The moves are the predictor's, but no strategy is needed to make
+        // them again
+        let replay = [format!("replay={}", path)];
+        let answers = solve(&replay).unwrap();
+        assert_eq!((Some(344.into()), Some(17336.into())),
+                   (answers.part1, answers.part2));
+        assert!(answers.report.unwrap().starts_with("Replayed "));
+
+        // A different move makes for a different game
+        let text = std::fs::read_to_string(path).unwrap();
+        let first = text.lines().find(|l| l.starts_with("in ")).unwrap();
+        let changed = first.replace(" 0", " 1").replace(" -1", " 0");
+        assert_ne!(first, changed);
+        std::fs::write(path, text.replacen(first, &changed, 1)).unwrap();
+        let error = solve(&replay).err().unwrap().to_string();
+        std::fs::remove_file(path).unwrap();
+        assert!(error.starts_with("Replay diverged"), "{}", error);
+    }
+
+    #[test]
+    fn test_reports() {
+        let solve = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|a| a.to_string())
+                                        .collect();
+            let mut solver = Solver::default();
+            solver.configure(&Options::parse(&args).unwrap()).unwrap();
+            solver.solve(&mut Stopwatch::new()).unwrap()
+        };
+        let answers = solve(&["moves=10"]);
+        assert_eq!(Some(344.into()), answers.part1);
+        assert_eq!(None, answers.part2);
+        assert!(answers.report.unwrap().starts_with("Score after 10 moves: "));
+
+        let answers = solve(&["compare", "moves=10"]);
+        assert_eq!((None, None), (answers.part1, answers.part2));
+        let report = answers.report.unwrap();
+        assert_eq!(1 + STRATEGIES.len(), report.lines().count());
+        assert!(report.lines().skip(1).all(|line| line.contains(" 10 ")),
+                "{}", report);
     }
 }
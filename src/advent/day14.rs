@@ -1,4 +1,5 @@
-use crate::advent::AdventSolver;
+use crate::advent::{AdventSolver, Answers};
+use crate::shared::stopwatch::Stopwatch;
 use anyhow::{Error, format_err};
 use lazy_static::lazy_static;
 use num_integer::Integer;
@@ -29,11 +30,13 @@ lazy_static! {
 }
 
 impl AdventSolver for Solver {
-    fn solve(&mut self) -> Result<(), Error> {
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, Error> {
         self.reactions =
             Solver::read_reactions_from_path("input/day14.txt")?;
-        println!("ORE required for 1 FUEL: {:?}",
-                 self.ore_to_produce(1, "FUEL"));
+        stopwatch.parsed();
+        let ore_per_fuel = self.ore_to_produce(1, "FUEL");
+        stopwatch.part1_done();
 
         // Leeroy Jenkinsssssssssssssss!!!!
         let supply = 1_000_000_000_000;
@@ -53,9 +56,8 @@ impl AdventSolver for Solver {
             }
         }
 
-        println!("Produced {} FUEL with 1 trillion ORE.", min);
-
-        Ok(())
+        // ORE required for 1 FUEL, and FUEL produced with 1 trillion ORE
+        Ok(Answers::new(ore_per_fuel, min))
     }
 }
 
@@ -251,4 +253,10 @@ mod tests {
         let mut solver = build_solver(text);
         assert_eq!(2210736, solver.ore_to_produce(1, "FUEL"));
     }
+
+    #[test]
+    fn test_answers() {
+        assert_eq!(Answers::new(362713, 3281820),
+                   Solver::default().solve(&mut Stopwatch::new()).unwrap());
+    }
 }
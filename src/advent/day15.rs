@@ -1,18 +1,38 @@
-use crate::advent::AdventSolver;
-use crate::shared::intcode::{Program, Simulator, Sender, Receiver};
-use crate::shared::grid::{InfiniteGrid, Dir, Pos};
+use crate::advent::{AdventSolver, Answers};
+use crate::shared::asciicast::RecordingOptions;
+use crate::shared::intcode::{Program, Recording, Simulator};
+use crate::shared::explorer::{Explorer, Protocol};
+use crate::shared::grid::{Dir, Pos};
+use crate::shared::options::Options;
+use crate::shared::stopwatch::Stopwatch;
 use anyhow::{Error, format_err};
 use num_derive::FromPrimitive;
 use num_traits::cast::FromPrimitive;
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::hash_map::Entry;
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
 
+// Options:
+//   headless    don't show the droid exploring, only the finished map
+//   fps=N       droid moves shown per second (default 100)
+//   cast=PATH   record the oxygen spreading through the ship to an asciicast
+//   frames=PATH write the map after each minute of the fill as plain text
+//   record=PATH save the droid's inputs and outputs on the shortest way to the
+//               oxygen system to PATH, to replay later
+//   replay=PATH make the moves saved in PATH again, and check that the droid
+//               answers the same way
+#[derive(Default)]
 pub struct Solver {
-    sim: Simulator,
-    grid: InfiniteGrid<ShipSpace>,
-    robot: Pos,
+    headless: bool,
+    fps: u32,
+    recording: RecordingOptions,
+    record: Option<String>,
+    replay: Option<String>,
 }
 
-#[derive(Copy,Clone,Eq,PartialEq)]
+#[derive(Copy,Clone,Debug,Eq,PartialEq)]
 enum ShipSpace {
     Unknown,
     Start,
@@ -28,44 +48,30 @@ enum MovementResponse {
     FoundOxygenSystem,
 }
 
-impl Default for Solver {
-    fn default() -> Self {
-        Solver {
-            sim: Simulator::new(),
-            grid: InfiniteGrid::new(ShipSpace::Unknown),
-            robot: Pos::origin(),
-        }
-    }
-}
-
-impl AdventSolver for Solver {
-    fn solve(&mut self) -> Result<(), Error> {
-        let program = Program::from_path("input/day15.txt")?;
-        self.sim.load_program(&program);
+// The repair droid's side of the explorer protocol.
+struct Droid;
 
-        // Robot does not start inside a wall
-        self.grid[self.robot] = ShipSpace::Start;
+type Ship = Explorer<Droid>;
 
-        // Perform a depth-first search to map the ship
-        let input = self.sim.create_input_channel();
-        let output = self.sim.create_output_channel();
-        self.map_ship(&input, &output)?;
-        assert_eq!(Pos::origin(), self.robot);
+// Marks for the live view.
+const DROID: &str = "\x1B[33;1mD\x1B[0m";
+const FRONTIER: &str = "\x1B[36m+\x1B[0m";
+const PATH: &str = "\x1B[32m.\x1B[0m";
 
-        self.show_grid();
-        println!("Steps to oxygen system: {}",
-                 self.steps_to_oxygen(Pos::origin()));
+impl Protocol for Droid {
+    type Cell = ShipSpace;
 
-        println!("Time to fill with oxygen: {}",
-                 self.fill_with_oxygen());
+    fn unknown(&self) -> ShipSpace {
+        ShipSpace::Unknown
+    }
 
-        Ok(())
+    // Robot does not start inside a wall
+    fn start(&self) -> ShipSpace {
+        ShipSpace::Start
     }
-}
 
-impl Solver {
     // Problem doesn't use the same order as my enum, hence:
-    fn dir_to_int(dir: &Dir) -> isize {
+    fn encode(&self, dir: Dir) -> isize {
         match dir {
             Dir::Up => 1,
             Dir::Down => 2,
@@ -74,132 +80,359 @@ impl Solver {
         }
     }
 
-    // Recursively explore any unmapped neighbors of robot
-    fn map_ship(&mut self, input: &Sender,
-                output: &Receiver) -> Result<(), Error> {
-        for &dir in [Dir::Up, Dir::Down, Dir::Left, Dir::Right].iter() {
-            let neighbor = self.robot.neighbor(dir);
-            if self.grid[neighbor] == ShipSpace::Unknown {
-                input.send(Self::dir_to_int(&dir))?;
-                self.sim.run()?;
-                let mut moved = false;
-                let o = output.recv()?;
-                let r = MovementResponse::from_isize(o)
-                            .ok_or(format_err!("Bad response: {}", o))?;
-                match r {
-                    MovementResponse::HitWall => {
-                        self.grid[neighbor] = ShipSpace::Wall;
-                    },
-                    MovementResponse::Success => {
-                        moved = true;
-                        self.grid[neighbor] = ShipSpace::Empty;
-                        self.robot = neighbor;
-                    },
-                    MovementResponse::FoundOxygenSystem => {
-                        moved = true;
-                        self.grid[neighbor] = ShipSpace::Oxygen;
-                        self.robot = neighbor;
-                    },
-                }
-                if moved {
-                    let reverse = dir.reverse();
-                    self.map_ship(input, output)?;
-                    input.send(Self::dir_to_int(&reverse))?;
-                    self.robot = self.robot.neighbor(reverse); 
-                    self.sim.run()?;
-                    output.recv()?;
-                }
-            }
+    fn decode(&self, output: isize) -> Result<ShipSpace, Error> {
+        let r = MovementResponse::from_isize(output)
+                    .ok_or(format_err!("Bad response: {}", output))?;
+        Ok(match r {
+            MovementResponse::HitWall => ShipSpace::Wall,
+            MovementResponse::Success => ShipSpace::Empty,
+            MovementResponse::FoundOxygenSystem => ShipSpace::Oxygen,
+        })
+    }
+
+    fn is_open(&self, space: &ShipSpace) -> bool {
+        *space != ShipSpace::Wall
+    }
+}
+
+impl AdventSolver for Solver {
+    fn configure(&mut self, options: &Options) -> Result<(), Error> {
+        self.headless = options.flag("headless")?;
+        self.fps = options.get_or("fps", 100)?;
+        if self.fps == 0 {
+            return Err(format_err!("fps must be at least 1"));
         }
+        self.recording = RecordingOptions::configure(options)?;
+        self.record = options.get_as("record")?;
+        self.replay = options.get_as("replay")?;
         Ok(())
     }
 
-    // BFS
-    fn steps_to_oxygen(&self, start: Pos) -> usize {
-        let mut queue: VecDeque<(Pos, Dir, usize)> = VecDeque::new();
-        queue.push_back((start, Dir::Up, 0));
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, Error> {
+        let program = Program::from_path("input/day15.txt")?;
+        stopwatch.parsed();
+        let mut report = Vec::new();
+        if let Some(ref path) = self.replay {
+            let recording = Recording::from_path(path)?;
+            Simulator::with_program(&program).replay(&recording)?;
+            report.push(format!("Replayed {} moves from {}, with the same \
+                                 outputs", recording.inputs().count(), path));
+        }
+        // Only the live exploration shows the map.
+        let ship = if self.headless {
+            Self::map_ship(&program)?
+        } else {
+            self.explore_live(&program)?
+        };
+
+        // Steps to the oxygen system, and time to fill with oxygen
+        let steps = Self::steps_to_oxygen(&ship)
+                        .ok_or(format_err!("No oxygen system found"))?;
+        stopwatch.part1_done();
+        let minutes = Self::fill_with_oxygen(&ship);
+        report.extend(self.record_fill(&ship)?);
+        if let Some(ref path) = self.record {
+            Self::walk_to_oxygen(&program, &ship)?.save(path)?;
+            report.push(format!("Saved session of {} moves to {}",
+                                steps, path));
+        }
+
+        let mut answers = Answers::new(steps, minutes);
+        if !report.is_empty() {
+            answers.report = Some(report.join("\n"));
+        }
+        Ok(answers)
+    }
+}
+
+impl Solver {
+    fn map_ship(program: &Program) -> Result<Ship, Error> {
+        let mut ship = Explorer::new(Droid, Simulator::with_program(program));
+        ship.explore()?;
+        Ok(ship)
+    }
 
-        loop {
-            let (pos, orig_dir, dist) = queue.pop_front().unwrap();
-            if self.grid[pos] == ShipSpace::Oxygen {
-                return dist;
+    // Map the ship as map_ship does, redrawing the map after every move.
+    // Once done, the shortest path to the oxygen system is shown.
+    fn explore_live(&self, program: &Program) -> Result<Ship, Error> {
+        let mut ship = Explorer::new(Droid, Simulator::with_program(program));
+        let delay = Duration::from_secs(1) / self.fps;
+        let mut bounds = None;
+        print!("\x1B[?25l"); // Hide cursor
+        let result = loop {
+            let droid = match ship.step() {
+                Ok(Some(droid)) => droid,
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            };
+            // Start over when the map grows, so nothing is left behind
+            if ship.grid().bounds() != bounds {
+                bounds = ship.grid().bounds();
+                print!("\x1B[2J");
             }
-            for &dir in [Dir::Up, Dir::Down, Dir::Left, Dir::Right].iter() {
-                if dir == orig_dir && pos != start {
-                    continue;
-                }
-                let neighbor = pos.neighbor(dir);
-                match self.grid[neighbor] {
-                    ShipSpace::Wall => {},
-                    ShipSpace::Oxygen => return dist+1,
-                    _ => queue.push_back((neighbor, dir.reverse(), dist+1)),
-                }
+            print!("\x1B[H{}", Self::explorer_view(&ship, Some(droid)));
+            io::stdout().flush().unwrap_or(());
+            thread::sleep(delay);
+        };
+        print!("\x1B[H{}", Self::explorer_view(&ship, None));
+        println!("\x1B[?25h"); // Show cursor
+        result.map(|_| ship)
+    }
+
+    // The map with the droid (if still exploring) and the frontier marked,
+    // then a status line. When done, the path to the oxygen system is shown.
+    fn explorer_view(ship: &Ship, droid: Option<Pos>) -> String {
+        let frontier: HashSet<Pos> = ship.frontier().into_iter().collect();
+        let path: HashSet<Pos> = match (droid, Self::oxygen(ship)) {
+            (None, Some(oxygen)) => {
+                let moves = ship.path_to(oxygen).unwrap_or_default();
+                moves.iter().scan(Pos::origin(), |pos, &dir| {
+                    *pos = pos.neighbor(dir);
+                    Some(*pos)
+                }).filter(|&pos| pos != oxygen).collect()
+            },
+            _ => HashSet::new(),
+        };
+        let map = Self::render(ship, |pos| {
+            if Some(pos) == droid {
+                Some(DROID)
+            } else if frontier.contains(&pos) {
+                Some(FRONTIER)
+            } else if path.contains(&pos) {
+                Some(PATH)
+            } else {
+                None
             }
-        }
+        });
+        format!("{}Explored: {}  Frontier: {}\x1B[K\n",
+                map, ship.visited(), frontier.len())
     }
 
-    // This is pretty inefficient but whatevs
-    fn fill_with_oxygen(&self) -> usize {
-        let mut grid = self.grid.crop();
-        let mut time = 0;
+    fn oxygen(ship: &Ship) -> Option<Pos> {
+        ship.reached().find(|&pos| ship.grid()[pos] == ShipSpace::Oxygen)
+    }
 
-        while !Self::is_filled(&grid) {
-            let mut to_fill = Vec::new();
-            for row in 0..grid.len() {
-                for col in 0..grid[row].len() {
-                    if grid[row][col] == ShipSpace::Oxygen {
-                        if row > 0 {
-                            to_fill.push((row-1, col));
-                        }
-                        if col > 0 {
-                            to_fill.push((row, col-1));
-                        }
-                        if row < grid.len()-1 {
-                            to_fill.push((row+1, col));
-                        }
-                        if col < grid[row].len()-1 {
-                            to_fill.push((row, col+1));
-                        }
-                    }
-                }
+    fn steps_to_oxygen(ship: &Ship) -> Option<usize> {
+        ship.distance(Self::oxygen(ship)?)
+    }
+
+    // A session of a fresh droid taking the shortest path to the oxygen
+    // system. (Exploring goes back and forth between snapshots of the droid,
+    // so can't be replayed from the start.)
+    fn walk_to_oxygen(program: &Program,
+                      ship: &Ship) -> Result<Recording, Error> {
+        let path = Self::oxygen(ship).and_then(|pos| ship.path_to(pos))
+                       .ok_or(format_err!("No oxygen system found"))?;
+        let mut sim = Simulator::with_program(program);
+        let input = sim.create_input_channel();
+        let _output = sim.create_output_channel();
+        sim.set_blocking_input(false);
+        for &dir in path.iter() {
+            input.send(Droid.encode(dir))?;
+        }
+        sim.start_recording();
+        sim.run()?;
+        Ok(sim.stop_recording().unwrap_or_default())
+    }
+
+    // The minute each open space gets oxygen, spreading one space a minute
+    // from the oxygen system (minute 0).
+    fn oxygen_timeline(ship: &Ship) -> HashMap<Pos, usize> {
+        let mut timeline = HashMap::new();
+        let mut queue = VecDeque::new();
+        for pos in ship.reached() {
+            if ship.grid()[pos] == ShipSpace::Oxygen {
+                timeline.insert(pos, 0);
+                queue.push_back(pos);
             }
-            for (row, col) in to_fill {
-                match grid[row][col] {
+        }
+        while let Some(pos) = queue.pop_front() {
+            let minute = timeline[&pos] + 1;
+            for &dir in [Dir::Up, Dir::Down, Dir::Left, Dir::Right].iter() {
+                let neighbor = pos.neighbor(dir);
+                match ship.grid()[neighbor] {
                     ShipSpace::Empty | ShipSpace::Start => {
-                        grid[row][col] = ShipSpace::Oxygen;
+                        if let Entry::Vacant(entry) = timeline.entry(neighbor) {
+                            entry.insert(minute);
+                            queue.push_back(neighbor);
+                        }
                     },
                     _ => {},
                 }
             }
-            time += 1;
         }
-        time
+        timeline
+    }
+
+    fn fill_with_oxygen(ship: &Ship) -> usize {
+        Self::oxygen_timeline(ship).values().cloned().max().unwrap_or(0)
     }
 
-    fn is_filled(grid: &Vec<Vec<ShipSpace>>) -> bool {
-        for row in grid {
-            for &col in row {
-                if col == ShipSpace::Empty || col == ShipSpace::Start {
-                    return false;
+    // The map at the end of each minute of the fill, oxygen shown as 'o'.
+    fn fill_frames(ship: &Ship, timeline: &HashMap<Pos, usize>)
+        -> Vec<String> {
+        let minutes = timeline.values().cloned().max().unwrap_or(0);
+        (0..=minutes).map(|minute| {
+            let map = Self::render(ship, |pos| {
+                match timeline.get(&pos) {
+                    Some(&t) if t <= minute && t > 0 => Some("o"),
+                    _ => None,
                 }
-            }
+            });
+            format!("{}Minute {}\n", map, minute)
+        }).collect()
+    }
+
+    // Returns what recordings were saved.
+    fn record_fill(&self, ship: &Ship) -> Result<Vec<String>, Error> {
+        let mut recorder = match self.recording.recorder() {
+            Some(recorder) => recorder,
+            None => return Ok(Vec::new()),
+        };
+        let timeline = Self::oxygen_timeline(ship);
+        recorder.write("\x1B[2J"); // Clear screen
+        for frame in Self::fill_frames(ship, &timeline) {
+            recorder.write(&format!("\x1B[H{}", frame));
+            recorder.advance(Duration::from_millis(50));
         }
-        true
-    }
-
-    fn show_grid(&self) {
-        let grid = self.grid.crop();
-        for row in 0..grid.len() {
-            for col in 0..grid[row].len() {
-                print!("{}", match grid[row][col] {
-                    ShipSpace::Unknown => "?",
-                    ShipSpace::Empty => " ",
-                    ShipSpace::Start => "S",
-                    ShipSpace::Oxygen => "O",
-                    ShipSpace::Wall => "#",
+        self.recording.save(&mut recorder)
+    }
+
+    // The part of the map explored, with marks (which may include escape
+    // sequences) drawn over some positions.
+    fn render<F>(ship: &Ship, mark: F) -> String
+        where F: Fn(Pos) -> Option<&'static str> {
+        let mut text = String::new();
+        let (top_left, bottom_right) = match ship.grid().bounds() {
+            Some(bounds) => bounds,
+            None => return text,
+        };
+        for row in top_left.row..=bottom_right.row {
+            for col in top_left.col..=bottom_right.col {
+                let pos = Pos::new(row, col);
+                text.push_str(match (mark(pos), ship.grid()[pos]) {
+                    (Some(mark), _) => mark,
+                    (None, ShipSpace::Unknown) => "?",
+                    (None, ShipSpace::Empty) => " ",
+                    (None, ShipSpace::Start) => "S",
+                    (None, ShipSpace::Oxygen) => "O",
+                    (None, ShipSpace::Wall) => "#",
                 });
             }
-            println!("");
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shared::intcode::IoEvent;
+
+    #[test]
+    fn test_answers() {
+        let program = Program::from_path("input/day15.txt").unwrap();
+        let ship = Solver::map_ship(&program).unwrap();
+        assert_eq!(Some(208), Solver::steps_to_oxygen(&ship));
+        assert_eq!(306, Solver::fill_with_oxygen(&ship));
+
+        // Following the path from the start leads to the oxygen system
+        let oxygen = Solver::oxygen(&ship).unwrap();
+        let path = ship.path_to(oxygen).unwrap();
+        let end = path.iter().fold(Pos::origin(), |pos, &d| pos.neighbor(d));
+        assert_eq!(oxygen, end);
+        assert!(path.iter().scan(Pos::origin(), |pos, &d| {
+            *pos = pos.neighbor(d);
+            Some(*pos)
+        }).all(|pos| ship.grid()[pos] != ShipSpace::Wall));
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let path = std::env::temp_dir()
+            .join(format!("advent2019_day15_{}.session", std::process::id()));
+        let path = path.to_str().unwrap();
+        let solve = |arg: String| {
+            let args = vec!["headless".to_string(), arg];
+            let mut solver = Solver::default();
+            solver.configure(&Options::parse(&args).unwrap()).unwrap();
+            solver.solve(&mut Stopwatch::new())
+        };
+        let answers = solve(format!("record={}", path)).unwrap();
+        assert_eq!(Some(208.into()), answers.part1);
+
+        // The droid reports finding the oxygen system on the last move
+        let recording = Recording::from_path(path).unwrap();
+        assert_eq!(208, recording.inputs().count());
+        match recording.events().last() {
+            Some(IoEvent::Output { value: 2, .. }) => {},
+            last => panic!("Last event: {:?}", last),
         }
+        let answers = solve(format!("replay={}", path)).unwrap();
+        assert_eq!(Some(306.into()), answers.part2);
+        assert!(answers.report.unwrap().starts_with("Replayed 208 moves"));
+
+        // A session the droid doesn't agree with is noticed
+        let text = recording.to_string();
+        assert!(text.ends_with(" 2\n"));
+        std::fs::write(path, format!("{}1\n", &text[..text.len() - 2]))
+            .unwrap();
+        let error = solve(format!("replay={}", path)).err().unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(error.to_string().starts_with("Replay diverged at event 415"),
+                "{}", error);
+    }
+
+    #[test]
+    fn test_oxygen_timeline() {
+        let program = Program::from_path("input/day15.txt").unwrap();
+        let ship = Solver::map_ship(&program).unwrap();
+        let timeline = Solver::oxygen_timeline(&ship);
+        assert_eq!(ship.visited(), timeline.len());
+        assert_eq!(Some(&0), timeline.get(&Solver::oxygen(&ship).unwrap()));
+        // As far from the oxygen system as the oxygen system is from here
+        assert_eq!(Some(&208), timeline.get(&Pos::origin()));
+
+        let frames = Solver::fill_frames(&ship, &timeline);
+        assert_eq!(307, frames.len());
+        assert_eq!(0, frames[0].matches('o').count());
+        assert!(frames[0].contains('S') && frames[0].ends_with("Minute 0\n"));
+        assert!(!frames[208].contains('S') && frames[207].contains('S'));
+        assert_eq!(timeline.len() - 1, frames[306].matches('o').count());
+    }
+
+    #[test]
+    fn test_explorer_view() {
+        let program = Program::from_path("input/day15.txt").unwrap();
+        let mut ship = Explorer::new(Droid, Simulator::with_program(&program));
+        let mut droid = None;
+        for _ in 0..20 {
+            droid = ship.step().unwrap();
+        }
+        let view = Solver::explorer_view(&ship, droid);
+        assert_eq!(1, view.matches(DROID).count());
+        assert_eq!(ship.frontier().len(), view.matches(FRONTIER).count());
+        let status = format!("Explored: {}  Frontier: {}\x1B[K\n",
+                             ship.visited(), ship.frontier().len());
+        assert!(view.ends_with(&status));
+        assert!(!view.contains(PATH));
+
+        ship.explore().unwrap();
+        let view = Solver::explorer_view(&ship, None);
+        assert!(!view.contains(DROID) && !view.contains(FRONTIER));
+        // Every space on the way but the start and the oxygen system
+        assert_eq!(207, view.matches(PATH).count());
+        assert!(view.contains('S') && view.contains('O'));
+    }
+
+    #[test]
+    fn test_solve() {
+        let mut solver = Solver::default();
+        solver.configure(&Options::parse(&["headless".to_string()]).unwrap())
+              .unwrap();
+        assert_eq!(Answers::new(208, 306),
+                   solver.solve(&mut Stopwatch::new()).unwrap());
     }
 }
@@ -1,6 +1,172 @@
+use crate::shared::ocr;
+use crate::shared::options::Options;
+use crate::shared::stopwatch::Stopwatch;
+use anyhow::format_err;
+use std::fmt;
+
 trait AdventSolver {
-    fn solve(&mut self) -> Result<(), anyhow::Error>;
+    // Take in any options given on the command line before solving.
+    fn configure(&mut self, _options: &Options) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    // Mark the ends of parsing and of part 1 on the stopwatch, where those
+    // are separate steps.
+    fn solve(&mut self, stopwatch: &mut Stopwatch)
+        -> Result<Answers, anyhow::Error>;
+}
+
+// What a day's solver found, for the caller to print or check. A part can be
+// missing when the solver was asked to do something else instead, which it
+// can tell about in the report.
+#[derive(Debug,Default,PartialEq)]
+pub struct Answers {
+    pub part1: Option<Answer>,
+    pub part2: Option<Answer>,
+    pub report: Option<String>,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub enum Answer {
+    Number(isize),
+    Text(String),
+    // Rows of pixels, true where lit. Usually letters to be read.
+    Image(Vec<Vec<bool>>),
+}
+
+impl Answers {
+    pub fn new<A: Into<Answer>, B: Into<Answer>>(part1: A, part2: B) -> Self {
+        Answers {
+            part1: Some(part1.into()),
+            part2: Some(part2.into()),
+            report: None,
+        }
+    }
+}
+
+// Images show as the letters they spell out, if they can be read.
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Answer::Number(n) => write!(f, "{}", n),
+            Answer::Text(text) => write!(f, "{}", text),
+            Answer::Image(pixels) => match ocr::read_letters(pixels) {
+                Ok(text) => write!(f, "{}", text),
+                Err(_) => write!(f, "(unreadable image)"),
+            },
+        }
+    }
+}
+
+// Plain integer literals, mostly for tests.
+impl From<i32> for Answer {
+    fn from(n: i32) -> Self {
+        Answer::Number(n as isize)
+    }
+}
+
+impl From<isize> for Answer {
+    fn from(n: isize) -> Self {
+        Answer::Number(n)
+    }
+}
+
+impl From<usize> for Answer {
+    fn from(n: usize) -> Self {
+        Answer::Number(n as isize)
+    }
+}
+
+impl From<u64> for Answer {
+    fn from(n: u64) -> Self {
+        Answer::Number(n as isize)
+    }
+}
+
+impl From<String> for Answer {
+    fn from(text: String) -> Self {
+        Answer::Text(text)
+    }
+}
+
+impl From<Vec<Vec<bool>>> for Answer {
+    fn from(pixels: Vec<Vec<bool>>) -> Self {
+        Answer::Image(pixels)
+    }
+}
+
+// Execute the solution for a particular day (1..25), timing it with the
+// stopwatch. Options the day doesn't know are an error if strict, otherwise
+// they're left for the caller to check (when running several days, say).
+pub fn solve(day: usize, options: &Options, strict: bool,
+             stopwatch: &mut Stopwatch) -> Result<Answers, anyhow::Error> {
+    let mut solver = solver(day)?;
+    solver.configure(options)?;
+    if let (true, Some(key)) = (strict, options.unread().first()) {
+        return Err(format_err!("Unknown option for day {}: {}", day, key));
+    }
+    stopwatch.restart();
+    let answers = solver.solve(stopwatch);
+    stopwatch.stop();
+    answers
+}
+
+// Days given as a list of days and ranges, e.g. "1-5,8,10-12", in order and
+// without repeats. Ranges may cover days without a solution; single days may
+// not.
+pub fn parse_days(spec: &str) -> Result<Vec<usize>, anyhow::Error> {
+    let number = |s: &str| {
+        s.trim().parse::<usize>()
+         .map_err(|_| format_err!("Bad day: '{}'", s.trim()))
+    };
+    let mut days = Vec::new();
+    for part in spec.split(',') {
+        match part.find('-') {
+            Some(pos) => {
+                let (first, last) = (number(&part[..pos])?,
+                                     number(&part[pos+1..])?);
+                days.extend(DAYS.iter().filter(|&&d| first <= d && d <= last));
+            },
+            None => {
+                let day = number(part)?;
+                if !DAYS.contains(&day) {
+                    return Err(format_err!("No solution for day {}", day));
+                }
+                days.push(day);
+            },
+        }
+    }
+    days.sort();
+    days.dedup();
+    Ok(days)
 }
 
 // Generated by build.rs
 include!("_all_days.rs");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_days() {
+        assert_eq!(vec![1, 2, 3, 5, 8], parse_days("5,1-3,8,2").unwrap());
+        assert_eq!(DAYS.to_vec(), parse_days("0-25").unwrap());
+        assert_eq!("No solution for day 26",
+                   parse_days("26").unwrap_err().to_string());
+        assert_eq!("Bad day: 'x'", parse_days("1-x").unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_unknown_options() {
+        let options = Options::parse(&["typo".to_string()]).unwrap();
+        let mut stopwatch = Stopwatch::new();
+        assert_eq!("Unknown option for day 1: typo",
+                   solve(1, &options, true, &mut stopwatch).unwrap_err()
+                                                          .to_string());
+        assert!(solve(1, &options, false, &mut stopwatch).is_ok());
+        assert_eq!("Invalid day number: 30",
+                   solve(30, &options, false, &mut stopwatch).unwrap_err()
+                                                           .to_string());
+    }
+}
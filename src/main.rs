@@ -1,25 +1,174 @@
 mod advent;
 mod shared;
 
-use argparse::{ArgumentParser, StoreOption};
+use advent::{Answer, Answers};
+use argparse::{ArgumentParser, Collect, StoreOption, StoreTrue};
+use shared::bitmap;
+use shared::intcode::Program;
+use shared::options::Options;
+use shared::stopwatch::{Stopwatch, Timings};
+use std::time::Duration;
+
+// Options that draw on the terminal or write a file, which don't go with
+// timing several days (and would have every day write to the same file).
+const SINGLE_DAY_OPTIONS: &[&str] = &[
+    "ascii", "cast", "frames", "inspect", "pbm", "play", "ppm", "record",
+    "render", "replay", "save",
+];
 
 fn main() {
     let mut day: Option<usize> = None;
+    let mut days: Option<String> = None;
+    let mut all = false;
+    let mut transpile: Option<String> = None;
+    let mut options: Vec<String> = Vec::new();
     {
         let mut parser = ArgumentParser::new();
         parser.set_description("Advent of Code 2019");
         parser.refer(&mut day)
               .add_option(&["-d", "--day"], StoreOption,
                           "number of challenge to run");
+        parser.refer(&mut days)
+              .add_option(&["--days"], StoreOption,
+                          "challenges to run and time, e.g. 1-5,8");
+        parser.refer(&mut all)
+              .add_option(&["-a", "--all"], StoreTrue,
+                          "run and time every challenge");
+        parser.refer(&mut options)
+              .add_option(&["-o", "--option"], Collect,
+                          "option for the challenge as KEY=VALUE \
+                           (repeatable)");
+        parser.refer(&mut transpile)
+              .add_option(&["--transpile"], StoreOption,
+                          "print an Intcode program (file) as Rust source");
         parser.parse_args_or_exit();
     }
+    if let Some(ref path) = transpile {
+        match Program::from_path(path) {
+            Ok(program) => print!("{}", program.to_rust()),
+            Err(e) => println!("error: {}", e),
+        }
+        return;
+    }
+    let mut options = match Options::parse(&options) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("error: {}", e);
+            return;
+        },
+    };
+    if all || days.is_some() {
+        let days = match days {
+            Some(ref spec) if !all => advent::parse_days(spec),
+            _ => Ok(advent::DAYS.to_vec()),
+        };
+        let single = SINGLE_DAY_OPTIONS.iter()
+                                       .find(|&&k| options.get(k).is_some());
+        match (days, single) {
+            (Err(e), _) => println!("error: {}", e),
+            (_, Some(key)) => {
+                println!("error: Option {} only works with --day", key);
+            },
+            (Ok(days), None) => {
+                // Time the solutions, not animations
+                options.force("headless", "true");
+                run_days(&days, &options);
+            },
+        }
+        return;
+    }
     match day {
-        Some(ref day) => {
-            match advent::solve(*day) {
-                Ok(_) => {},
+        Some(day) => {
+            let mut stopwatch = Stopwatch::new();
+            match advent::solve(day, &options, true, &mut stopwatch) {
+                Ok(answers) => print_answers(&answers),
                 Err(e) => println!("error: {}", e)
             }
         },
-        None => println!("--day is required"),
+        None => println!("--day, --days or --all is required"),
+    }
+}
+
+// Images are drawn before the letters read from them.
+fn print_answers(answers: &Answers) {
+    if let Some(ref report) = answers.report {
+        println!("{}", report);
+    }
+    for (part, answer) in [(1, &answers.part1), (2, &answers.part2)].iter() {
+        if let Some(answer) = answer {
+            if let Answer::Image(pixels) = answer {
+                print!("\n{}\n", bitmap::to_ascii(pixels, '\u{2588}', ' '));
+            }
+            println!("Part {}: {}", part, answer);
+        }
+    }
+}
+
+// Solve each day in turn, carrying on after any that fail, then show how long
+// they took. Options go to whichever days know them.
+fn run_days(days: &[usize], options: &Options) {
+    let mut results = Vec::new();
+    for &day in days {
+        println!("Day {}", day);
+        let mut stopwatch = Stopwatch::new();
+        let result = advent::solve(day, options, false, &mut stopwatch);
+        match result {
+            Ok(ref answers) => print_answers(answers),
+            Err(ref e) => println!("error: {}", e),
+        }
+        println!();
+        results.push((day, stopwatch.timings(), result));
+    }
+
+    let row = |day: &str, t: &Timings, result: &str| {
+        println!("{:<5}{:>10}{:>10}{:>10}{:>10}  {}", day,
+                 format_time(t.parse), format_time(t.part1),
+                 format_time(Some(t.part2)), format_time(Some(t.total)),
+                 result);
+    };
+    println!("{:<5}{:>10}{:>10}{:>10}{:>10}  Answers",
+             "Day", "Parse", "Part 1", "Part 2", "Total");
+    let mut total = Timings::default();
+    let mut failed = 0;
+    for (day, timings, result) in results.iter() {
+        let summary = match result {
+            Ok(answers) => {
+                let show = |a: &Option<Answer>| match a {
+                    Some(answer) => answer.to_string(),
+                    None => "-".to_string(),
+                };
+                format!("{}, {}", show(&answers.part1), show(&answers.part2))
+            },
+            Err(e) => {
+                failed += 1;
+                format!("error: {}", e)
+            },
+        };
+        row(&day.to_string(), timings, &summary);
+        let add = |sum: Option<Duration>, time: Option<Duration>| {
+            match (sum, time) {
+                (Some(a), Some(b)) => Some(a + b),
+                (a, b) => a.or(b),
+            }
+        };
+        total.parse = add(total.parse, timings.parse);
+        total.part1 = add(total.part1, timings.part1);
+        total.part2 += timings.part2;
+        total.total += timings.total;
+    }
+    row("All", &total, &format!("{} solved, {} failed",
+                                 results.len() - failed, failed));
+
+    let unread = options.unread();
+    if !unread.is_empty() {
+        println!("warning: no day used option(s): {}", unread.join(", "));
+    }
+}
+
+fn format_time(time: Option<Duration>) -> String {
+    match time {
+        Some(t) if t.as_secs() >= 1 => format!("{:.2}s", t.as_secs_f64()),
+        Some(t) => format!("{:.1}ms", t.as_secs_f64() * 1000.0),
+        None => "-".to_string(),
     }
 }
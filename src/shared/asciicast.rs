@@ -0,0 +1,272 @@
+use super::options::Options;
+use anyhow::Error;
+use std::fs::File;
+use std::io::Write;
+use std::time::Duration;
+
+/* Records what an animation writes to the terminal, so it can be shared and
+ * replayed without running the solver again. Time is kept by the caller
+ * (advance() after each frame), which means a recording made without
+ * displaying anything still plays back at the intended speed.
+ *
+ * There are two outputs: an asciicast v2 file for asciinema, and a plain text
+ * dump of the screen after each frame. For the latter, output is played onto a
+ * simple virtual terminal that understands the escapes used in this project
+ * (cursor positioning and clearing); colors and the like are dropped. */
+pub struct Recorder {
+    clock: Duration,
+    events: Vec<(Duration, String)>,
+    screen: Vec<Vec<char>>,
+    // (row, column), 0-based.
+    cursor: (usize, usize),
+    // Size of the terminal needed to show everything written.
+    width: usize,
+    height: usize,
+    frames: Vec<(Duration, String)>,
+    changed: bool,
+}
+
+// Where to save a recording, as given by the options cast=PATH (asciicast)
+// and frames=PATH (plain frame dump).
+#[derive(Default)]
+pub struct RecordingOptions {
+    cast: Option<String>,
+    frames: Option<String>,
+}
+
+impl RecordingOptions {
+    pub fn configure(options: &Options) -> Result<Self, Error> {
+        Ok(RecordingOptions {
+            cast: options.get_as("cast")?,
+            frames: options.get_as("frames")?,
+        })
+    }
+
+    // A recorder if anything is to be saved.
+    pub fn recorder(&self) -> Option<Recorder> {
+        match self.cast.is_some() || self.frames.is_some() {
+            true => Some(Recorder::new()),
+            false => None,
+        }
+    }
+
+    // Returns what was saved where, for the caller to report.
+    pub fn save(&self, recorder: &mut Recorder) -> Result<Vec<String>, Error> {
+        let mut saved = Vec::new();
+        if let Some(ref path) = self.cast {
+            recorder.save_cast(path)?;
+            saved.push(format!("Saved recording to {}", path));
+        }
+        if let Some(ref path) = self.frames {
+            recorder.save_frames(path)?;
+            saved.push(format!("Saved frames to {}", path));
+        }
+        Ok(saved)
+    }
+}
+
+#[allow(dead_code)]
+impl Recorder {
+    pub fn new() -> Self {
+        Recorder {
+            clock: Duration::from_secs(0),
+            events: Vec::new(),
+            screen: Vec::new(),
+            cursor: (0, 0),
+            width: 1,
+            height: 1,
+            frames: Vec::new(),
+            changed: false,
+        }
+    }
+
+    // Record text written to the terminal at the current time.
+    pub fn write(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        match self.events.last_mut() {
+            Some((time, last)) if *time == self.clock => {
+                last.push_str(text);
+            },
+            _ => self.events.push((self.clock, text.to_string())),
+        }
+        self.play(text);
+        self.changed = true;
+    }
+
+    // Move time forward. Whatever was written since the last call makes up
+    // a frame.
+    pub fn advance(&mut self, duration: Duration) {
+        self.take_frame();
+        self.clock += duration;
+    }
+
+    pub fn save_cast(&self, path: &str) -> Result<(), Error> {
+        File::create(path)?.write_all(self.to_cast().as_bytes())?;
+        Ok(())
+    }
+
+    // Each frame starts with a line giving its number and time.
+    pub fn save_frames(&mut self, path: &str) -> Result<(), Error> {
+        self.take_frame();
+        let mut file = File::create(path)?;
+        for (i, (time, frame)) in self.frames.iter().enumerate() {
+            write!(file, "--- frame {} at {:.3}s\n{}", i + 1,
+                   time.as_secs_f64(), frame)?;
+        }
+        Ok(())
+    }
+
+    pub fn to_cast(&self) -> String {
+        let mut cast = format!("{{\"version\": 2, \"width\": {}, \
+                                \"height\": {}}}\n", self.width, self.height);
+        for (time, text) in self.events.iter() {
+            // A terminal would turn newlines into CR LF on output.
+            let text = text.replace("\r\n", "\n").replace('\n', "\r\n");
+            cast.push_str(&format!("[{:.6}, \"o\", \"{}\"]\n",
+                                   time.as_secs_f64(), json_escape(&text)));
+        }
+        cast
+    }
+
+    // The screen as it is now, with trailing spaces removed.
+    pub fn screen(&self) -> String {
+        self.screen.iter()
+                   .map(|row| {
+                       let line: String = row.iter().collect();
+                       format!("{}\n", line.trim_end())
+                   })
+                   .collect()
+    }
+
+    fn take_frame(&mut self) {
+        if self.changed {
+            self.frames.push((self.clock, self.screen()));
+            self.changed = false;
+        }
+    }
+
+    fn play(&mut self, text: &str) {
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\x1B' => self.escape(&mut chars),
+                '\n' => self.cursor = (self.cursor.0 + 1, 0),
+                '\r' => self.cursor.1 = 0,
+                _ => {
+                    let (row, col) = self.cursor;
+                    self.put(row, col, c);
+                    self.cursor.1 += 1;
+                },
+            }
+        }
+    }
+
+    // Handle a CSI sequence (ESC [ params final); anything else is skipped.
+    fn escape(&mut self, chars: &mut std::str::Chars) {
+        if chars.next() != Some('[') {
+            return;
+        }
+        let mut params = String::new();
+        let command = loop {
+            match chars.next() {
+                Some(c) if c.is_ascii_digit() || c == ';' || c == '?' => {
+                    params.push(c);
+                },
+                Some(c) => break c,
+                None => return,
+            }
+        };
+        let numbers: Vec<usize> = params.split(';')
+                                        .map(|p| p.parse().unwrap_or(0))
+                                        .collect();
+        let arg = |i: usize| numbers.get(i).cloned().unwrap_or(0).max(1);
+        match command {
+            'H' => self.cursor = (arg(0) - 1, arg(1) - 1),
+            'A' => self.cursor.0 = self.cursor.0.saturating_sub(arg(0)),
+            'B' => self.cursor.0 += arg(0),
+            'C' => self.cursor.1 += arg(0),
+            'D' => self.cursor.1 = self.cursor.1.saturating_sub(arg(0)),
+            'J' if params == "2" => self.screen.clear(),
+            'K' => {
+                let (row, col) = self.cursor;
+                if let Some(line) = self.screen.get_mut(row) {
+                    line.truncate(col);
+                }
+            },
+            // Colors, showing and hiding the cursor etc.
+            _ => {},
+        }
+    }
+
+    fn put(&mut self, row: usize, col: usize, c: char) {
+        if self.screen.len() <= row {
+            self.screen.resize(row + 1, Vec::new());
+        }
+        let line = &mut self.screen[row];
+        if line.len() <= col {
+            line.resize(col + 1, ' ');
+        }
+        line[col] = c;
+        self.width = self.width.max(col + 1);
+        self.height = self.height.max(row + 1);
+    }
+}
+
+fn json_escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 || c == '\x7F' => {
+                escaped.push_str(&format!("\\u{:04x}", c as u32));
+            },
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn virtual_terminal() {
+        let mut recorder = Recorder::new();
+        recorder.write("\x1B[2J\x1B[?25l\x1B[36;1mScore: 0\n#..#\n.##.\n");
+        recorder.write("\x1B[3;2H\x1B[31m@\x1B[0m\x1B[1;8H5\x1B[K");
+        assert_eq!("Score: 5\n#..#\n.@#.\n", recorder.screen());
+        recorder.write("\x1B[2J\x1B[H\"x\"");
+        assert_eq!("\"x\"\n", recorder.screen());
+    }
+
+    #[test]
+    fn cast_and_frames() {
+        let mut recorder = Recorder::new();
+        recorder.write("\x1B[Hab\n");
+        recorder.write("cd");
+        recorder.advance(Duration::from_millis(250));
+        recorder.advance(Duration::from_millis(250));
+        recorder.write("\x1B[1;1HA\\");
+        assert_eq!("{\"version\": 2, \"width\": 2, \"height\": 2}\n\
+                    [0.000000, \"o\", \"\\u001b[Hab\\r\\ncd\"]\n\
+                    [0.500000, \"o\", \"\\u001b[1;1HA\\\\\"]\n",
+                   recorder.to_cast());
+
+        let path = std::env::temp_dir()
+            .join(format!("advent2019_frames_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        recorder.save_frames(path).unwrap();
+        let frames = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!("--- frame 1 at 0.000s\nab\ncd\n\
+                    --- frame 2 at 0.500s\nA\\\ncd\n",
+                   frames);
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::Error;
+use std::fs::File;
+use std::io::Write;
+
+/* Exports of two-color pictures (rows of pixels, true where something is
+ * drawn), such as the messages the puzzles spell out, to files that can be
+ * looked at without a terminal: plain PBM and PPM images, and ASCII art.
+ * Images can be scaled up, since a 6 pixel tall message is hard to see. */
+
+pub type Rgb = (u8, u8, u8);
+
+// Netpbm plain formats don't allow lines longer than 70 characters.
+const MAX_LINE: usize = 70;
+
+// Drawn pixels are black, as in ink on paper.
+pub fn to_pbm(pixels: &[Vec<bool>], scale: usize) -> String {
+    let pixels = scaled(pixels, scale);
+    let mut text = format!("P1\n{}\n", dimensions(&pixels));
+    for row in pixels.iter() {
+        let values: Vec<&str> = row.iter()
+                                   .map(|&p| if p { "1" } else { "0" })
+                                   .collect();
+        text.push_str(&wrap(&values));
+    }
+    text
+}
+
+pub fn to_ppm(pixels: &[Vec<bool>], scale: usize,
+              drawn: Rgb, background: Rgb) -> String {
+    let pixels = scaled(pixels, scale);
+    let mut text = format!("P3\n{}\n255\n", dimensions(&pixels));
+    for row in pixels.iter() {
+        let values: Vec<String> = row.iter().map(|&p| {
+            let (r, g, b) = if p { drawn } else { background };
+            format!("{} {} {}", r, g, b)
+        }).collect();
+        let values: Vec<&str> = values.iter().map(|v| v.as_str()).collect();
+        text.push_str(&wrap(&values));
+    }
+    text
+}
+
+pub fn to_ascii(pixels: &[Vec<bool>], drawn: char, background: char)
+    -> String {
+    let glyph = |&p: &bool| if p { drawn } else { background };
+    pixels.iter()
+          .map(|row| row.iter().map(glyph).collect::<String>() + "\n")
+          .collect()
+}
+
+pub fn save(path: &str, contents: &str) -> Result<(), Error> {
+    File::create(path)?.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+fn scaled(pixels: &[Vec<bool>], scale: usize) -> Vec<Vec<bool>> {
+    let mut result = Vec::new();
+    for row in pixels.iter() {
+        let row: Vec<bool> = row.iter()
+                                .flat_map(|&p| std::iter::repeat_n(p, scale))
+                                .collect();
+        for _ in 0..scale {
+            result.push(row.clone());
+        }
+    }
+    result
+}
+
+// "width height", from the widest row.
+fn dimensions(pixels: &[Vec<bool>]) -> String {
+    let width = pixels.iter().map(|row| row.len()).max().unwrap_or(0);
+    format!("{} {}", width, pixels.len())
+}
+
+// Space separated values, broken into lines no longer than MAX_LINE.
+fn wrap(values: &[&str]) -> String {
+    let mut text = String::new();
+    let mut line = String::new();
+    for value in values {
+        if !line.is_empty() && line.len() + 1 + value.len() > MAX_LINE {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(value);
+    }
+    text.push_str(&line);
+    text.push('\n');
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_formats() {
+        let pixels = vec![vec![true, false], vec![false, true]];
+        assert_eq!("#.\n.#\n", to_ascii(&pixels, '#', '.'));
+        assert_eq!("P1\n2 2\n1 0\n0 1\n", to_pbm(&pixels, 1));
+        assert_eq!("P1\n4 4\n1 1 0 0\n1 1 0 0\n0 0 1 1\n0 0 1 1\n",
+                   to_pbm(&pixels, 2));
+        assert_eq!("P3\n2 2\n255\n255 255 255 0 0 0\n0 0 0 255 255 255\n",
+                   to_ppm(&pixels, 1, (255, 255, 255), (0, 0, 0)));
+
+        // Long rows are wrapped
+        let wide = vec![vec![true; 50]];
+        let pbm = to_pbm(&wide, 1);
+        assert!(pbm.lines().all(|line| line.len() <= MAX_LINE));
+        assert_eq!(50, pbm.lines().skip(2).map(|l| l.split(' ').count())
+                                          .sum::<usize>());
+    }
+}
@@ -0,0 +1,197 @@
+use super::grid::{Dir, InfiniteGrid, Pos};
+use super::intcode::{Receiver, Sender, Simulator, Snapshot};
+use anyhow::{Error, format_err};
+use std::collections::{HashMap, VecDeque};
+
+/* Explores an unknown map with a robot run by an Intcode program, for puzzles
+ * where the program is given a direction to move in and answers with what it
+ * found there. The details of that exchange are left to a Protocol.
+ *
+ * Exploration is breadth-first. Every open cell reached keeps a snapshot of
+ * the program from when the robot got there, and its neighbors are tried
+ * starting from that snapshot, so the robot never has to be walked back.
+ * Cells are reached in order of distance from the start, which makes the
+ * first path found to each one a shortest path. */
+pub struct Explorer<P: Protocol> {
+    protocol: P,
+    sim: Simulator,
+    input: Sender,
+    output: Receiver,
+    grid: InfiniteGrid<P::Cell>,
+    // Direction of the last move on a shortest path to each open cell
+    // reached, None for the start.
+    came_from: HashMap<Pos, Option<Dir>>,
+    // Open cells reached whose neighbors haven't been tried yet.
+    frontier: VecDeque<(Pos, Snapshot)>,
+}
+
+pub trait Protocol {
+    type Cell: Clone + PartialEq;
+
+    // What cells not explored yet hold, and what the starting cell holds.
+    fn unknown(&self) -> Self::Cell;
+    fn start(&self) -> Self::Cell;
+
+    // The program's input for a move in the given direction.
+    fn encode(&self, dir: Dir) -> isize;
+
+    // What the program's answer to a move says is in the cell moved towards.
+    fn decode(&self, output: isize) -> Result<Self::Cell, Error>;
+
+    // Whether the robot is now in a cell like this, rather than blocked.
+    fn is_open(&self, cell: &Self::Cell) -> bool;
+}
+
+const DIRS: [Dir; 4] = [Dir::Up, Dir::Down, Dir::Left, Dir::Right];
+
+#[allow(dead_code)]
+impl<P: Protocol> Explorer<P> {
+    // The robot starts at the origin, in whatever state sim is in.
+    pub fn new(protocol: P, mut sim: Simulator) -> Self {
+        let input = sim.create_input_channel();
+        let output = sim.create_output_channel();
+        let mut grid = InfiniteGrid::new(protocol.unknown());
+        grid[Pos::origin()] = protocol.start();
+        let mut came_from = HashMap::new();
+        came_from.insert(Pos::origin(), None);
+        let mut frontier = VecDeque::new();
+        frontier.push_back((Pos::origin(), sim.snapshot()));
+        Explorer {
+            protocol,
+            sim,
+            input,
+            output,
+            grid,
+            came_from,
+            frontier,
+        }
+    }
+
+    // Explore until there's nothing left to try.
+    pub fn explore(&mut self) -> Result<(), Error> {
+        while self.step()?.is_some() {}
+        Ok(())
+    }
+
+    // Try the unknown neighbors of the next cell on the frontier, and return
+    // that cell (where the robot is, as it were). None when done.
+    pub fn step(&mut self) -> Result<Option<Pos>, Error> {
+        let (pos, snapshot) = match self.frontier.pop_front() {
+            Some(next) => next,
+            None => return Ok(None),
+        };
+        for &dir in DIRS.iter() {
+            let neighbor = pos.neighbor(dir);
+            if self.grid[neighbor] != self.protocol.unknown() {
+                continue;
+            }
+            self.sim.restore(&snapshot);
+            self.input.send(self.protocol.encode(dir))?;
+            self.sim.run()?;
+            let output = self.output.try_recv().map_err(|_| {
+                format_err!("No response to move {:?} from {:?}", dir, pos)
+            })?;
+            let cell = self.protocol.decode(output)?;
+            let open = self.protocol.is_open(&cell);
+            self.grid[neighbor] = cell;
+            if open {
+                self.came_from.insert(neighbor, Some(dir));
+                self.frontier.push_back((neighbor, self.sim.snapshot()));
+            }
+        }
+        Ok(Some(pos))
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.frontier.is_empty()
+    }
+
+    pub fn grid(&self) -> &InfiniteGrid<P::Cell> {
+        &self.grid
+    }
+
+    // Open cells reached so far, the start included.
+    pub fn reached(&self) -> impl Iterator<Item=Pos> + '_ {
+        self.came_from.keys().cloned()
+    }
+
+    pub fn visited(&self) -> usize {
+        self.came_from.len()
+    }
+
+    // Cells reached but not explored from yet, nearest first.
+    pub fn frontier(&self) -> Vec<Pos> {
+        self.frontier.iter().map(|&(pos, _)| pos).collect()
+    }
+
+    // Moves from the start to pos along a shortest path, if pos was reached.
+    pub fn path_to(&self, pos: Pos) -> Option<Vec<Dir>> {
+        let mut path = Vec::new();
+        let mut pos = pos;
+        while let Some(dir) = *self.came_from.get(&pos)? {
+            path.push(dir);
+            pos = pos.neighbor(dir.reverse());
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    pub fn distance(&self, pos: Pos) -> Option<usize> {
+        self.path_to(pos).map(|path| path.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::intcode::Program;
+
+    // Day 15's droid, seeing only whether it could move.
+    struct Droid;
+
+    impl Protocol for Droid {
+        type Cell = Option<bool>;
+
+        fn unknown(&self) -> Option<bool> { None }
+        fn start(&self) -> Option<bool> { Some(true) }
+
+        fn encode(&self, dir: Dir) -> isize {
+            match dir {
+                Dir::Up => 1,
+                Dir::Down => 2,
+                Dir::Left => 3,
+                Dir::Right => 4,
+            }
+        }
+
+        fn decode(&self, output: isize) -> Result<Option<bool>, Error> {
+            Ok(Some(output != 0))
+        }
+
+        fn is_open(&self, cell: &Option<bool>) -> bool {
+            *cell == Some(true)
+        }
+    }
+
+    #[test]
+    fn explore_day15() {
+        let program = Program::from_path("input/day15.txt").unwrap();
+        let mut explorer = Explorer::new(Droid,
+                                         Simulator::with_program(&program));
+        assert_eq!(Some(Pos::origin()), explorer.step().unwrap());
+        let first = explorer.frontier();
+        assert!(!first.is_empty());
+        assert!(first.iter().all(|pos| explorer.distance(*pos) == Some(1)));
+        assert_eq!(first.len() + 1, explorer.visited());
+
+        explorer.explore().unwrap();
+        assert!(explorer.is_done());
+        assert!(explorer.frontier().is_empty());
+        assert_eq!(None, explorer.step().unwrap());
+        let farthest = explorer.reached()
+                               .map(|pos| explorer.distance(pos).unwrap())
+                               .max();
+        assert!(farthest > Some(208), "{:?}", farthest);
+        assert_eq!(None, explorer.path_to(Pos::new(1000, 1000)));
+    }
+}
@@ -17,11 +17,9 @@ impl<T: Clone+PartialEq> InfiniteGrid<T> {
         }
     }
 
-    // Output a finite, cropped grid in the form of a Vec of Vec. Crops the
-    // grid by ignoring any values that are still at the default. Contents are
-    // cloned. The result may actually be an empty Vec if there are no
-    // non-default values.
-    pub fn crop(&self) -> Vec<Vec<T>> {
+    // The top left and bottom right corners of the smallest rectangle holding
+    // all the non-default values, or None if there aren't any.
+    pub fn bounds(&self) -> Option<(Pos, Pos)> {
         // I'm sure this could be prettier >_>
 
         // Find bounds
@@ -61,13 +59,23 @@ impl<T: Clone+PartialEq> InfiniteGrid<T> {
             }
         }
 
-        // Create cropped grid
-        let mut result: Vec<Vec<T>> = Vec::new();
         if left.is_some() {
-            let left = left.unwrap();
-            let top = top.unwrap();
-            let right = right.unwrap();
-            let bottom = bottom.unwrap();
+            Some((Pos::new(top.unwrap(), left.unwrap()),
+                  Pos::new(bottom.unwrap(), right.unwrap())))
+        } else {
+            None
+        }
+    }
+
+    // Output a finite, cropped grid in the form of a Vec of Vec. Crops the
+    // grid by ignoring any values that are still at the default. Contents are
+    // cloned. The result may actually be an empty Vec if there are no
+    // non-default values.
+    pub fn crop(&self) -> Vec<Vec<T>> {
+        let mut result: Vec<Vec<T>> = Vec::new();
+        if let Some((top_left, bottom_right)) = self.bounds() {
+            let (top, left) = (top_left.row, top_left.col);
+            let (bottom, right) = (bottom_right.row, bottom_right.col);
             for y_pos in top..=bottom {
                 let y_index = Self::pos_to_index(y_pos);
                 let orig_row = self.rows.get(y_index).unwrap();
@@ -182,6 +190,7 @@ mod tests {
         assert!(grid[pos!(0, 0)]    == 'c');
         assert!(grid[pos!(14, -14)] == 'd');
         assert!(grid[pos!(-14, 14)] == 'e');
+        assert!(grid.bounds() == Some((pos!(-14, -14), pos!(14, 14))));
         
         // overwrite
         grid[pos!(0, 0)] = 'z';
@@ -1,8 +1,41 @@
 use anyhow::{Error, format_err};
 use std::fs::File;
+use std::collections::VecDeque;
 use std::io::Read;
 use std::sync::mpsc;
 
+mod call_stack;
+#[cfg(test)]
+mod fuzz;
+#[cfg(test)]
+mod golden;
+mod inspector;
+mod instruction_set;
+mod optimizer;
+mod parse;
+mod recording;
+mod snapshot;
+mod strict;
+pub mod symbolic;
+mod transpile;
+
+#[allow(unused_imports)]
+pub use call_stack::{Backtrace, Frame};
+#[allow(unused_imports)]
+pub use inspector::Inspector;
+#[allow(unused_imports)]
+pub use instruction_set::{InstructionSet, OpContext};
+#[allow(unused_imports)]
+pub use optimizer::Optimization;
+#[allow(unused_imports)]
+pub use parse::{Location, ParseError};
+#[allow(unused_imports)]
+pub use recording::{IoEvent, Recording};
+#[allow(unused_imports)]
+pub use snapshot::Snapshot;
+#[allow(unused_imports)]
+pub use strict::{StrictMode, Strictness, Violation, ViolationKind};
+
 pub type Receiver = mpsc::Receiver<isize>;
 pub type Sender = mpsc::Sender<isize>;
 
@@ -15,16 +48,25 @@ pub struct Simulator {
     mem: Vec<isize>,
     pc: usize,
     relative_base: usize,
+    steps: u64,
     io: SimulatorIO,
+    recording: Option<Recording>,
+    instruction_set: InstructionSet,
+    strict: strict::StrictState,
+    fused: optimizer::FusedTable,
+    history: inspector::History,
+    calls: call_stack::CallStack,
 }
 
 struct SimulatorIO {
     input_reader: Option<Receiver>,
+    // Values taken off the input channel but not consumed yet.
+    pending_input: VecDeque<isize>,
     output_sender: Option<Sender>,
     blocking_input: bool,
 }
 
-#[derive(Clone,PartialEq)]
+#[derive(Clone,Debug,PartialEq)]
 enum ProgramState {
     Running,
     Wait,
@@ -37,14 +79,14 @@ enum Parameter {
     Value(isize),
 }
 
-#[derive(Copy,Clone,PartialEq)]
+#[derive(Copy,Clone,Debug,PartialEq)]
 enum ParameterMode {
     Position,
     Immediate,
     Relative,
 }
 
-#[derive(Copy,Clone)]
+#[derive(Clone)]
 enum Op {
     Add { x: Parameter, y: Parameter, dest: Parameter },
     Multiply { x: Parameter, y: Parameter, dest: Parameter },
@@ -56,22 +98,34 @@ enum Op {
     Equal { x: Parameter, y: Parameter, dest: Parameter },
     AdjustRelativeBase { offset: Parameter },
     Halt,
+    Custom { opcode: isize, params: Vec<Parameter>, writes: Vec<usize> },
 }
 
 impl Program {
+    // Loads either format: binary if the file starts with the binary header,
+    // text otherwise.
     pub fn from_path(path: &str) -> Result<Self, Error> {
-        let mut text = String::new();
-        File::open(path)?.read_to_string(&mut text)?;
-        Self::from_string(text.trim())
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        if bytes.starts_with(parse::MAGIC) {
+            Self::from_bytes(&bytes)
+        } else {
+            Self::from_string(std::str::from_utf8(&bytes)?)
+        }
     }
 
     pub fn from_string(text: &str) -> Result<Self, Error> {
-        let inst: Vec<isize> = text.split(',')
-                                   .map(|s| s.parse::<isize>())
-                                   .collect::<Result<Vec<isize>, _>>()?;
-        Ok(Self {
-            instructions: inst,
-        })
+        Ok(parse::parse_text(text)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(parse::parse_binary(bytes)?)
+    }
+
+    // Encode in the compact binary format.
+    #[allow(dead_code)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        parse::to_binary(self)
     }
 }
 
@@ -82,7 +136,14 @@ impl Simulator {
             mem: Vec::new(),
             pc: 0,
             relative_base: 0,
+            steps: 0,
             io: SimulatorIO::default(),
+            recording: None,
+            instruction_set: InstructionSet::stock(),
+            strict: strict::StrictState::default(),
+            fused: optimizer::FusedTable::default(),
+            history: inspector::History::default(),
+            calls: call_stack::CallStack::default(),
         }
     }
 
@@ -93,6 +154,14 @@ impl Simulator {
         sim
     }
 
+    // Same, with the program's optimization installed: runs of instructions
+    // are replaced with fused instructions while their code is untouched.
+    pub fn with_optimized_program(program: &Program) -> Self {
+        let mut sim = Self::with_program(program);
+        sim.fused = program.optimize().table();
+        sim
+    }
+
     // Load the given program into memory. Implicitly resets all state to new
     // (I/O handlers are untouched).
     pub fn load_program(&mut self, program: &Program) {
@@ -100,6 +169,11 @@ impl Simulator {
         self.mem = program.instructions.clone();
         self.pc = 0;
         self.relative_base = 0;
+        self.steps = 0;
+        self.strict.reset(program.instructions.len());
+        self.fused = optimizer::FusedTable::default();
+        self.history.clear();
+        self.calls = call_stack::CallStack::default();
     }
 
     // Creates a channel for you and returns the sender half.
@@ -140,13 +214,15 @@ impl Simulator {
 
     // Overwrite memory at the given address.
     pub fn poke(&mut self, address: usize, value: isize) {
-        self.store(Parameter::Address(address), value).ok();
+        self.write(address, value);
     }
 
     pub fn run(&mut self) -> Result<(), Error> {
         self.state = ProgramState::Running;
         while self.is_running() {
-            self.step()?;
+            if let Err(e) = self.step() {
+                return Err(self.fault(e));
+            }
             if self.state == ProgramState::Wait {
                 break;
             }
@@ -163,37 +239,125 @@ impl Simulator {
         self.io.blocking_input = blocking;
     }
 
+    // Enable checks for suspicious memory accesses. Set this before poking
+    // anything beyond the program image, so those writes are seen.
+    #[allow(dead_code)]
+    pub fn set_strict_mode(&mut self, mode: StrictMode) {
+        self.strict.mode = mode;
+    }
+
+    // Violations found by checks set to Strictness::Warn.
+    #[allow(dead_code)]
+    pub fn warnings(&self) -> &Vec<Violation> {
+        &self.strict.warnings
+    }
+
+    // Use a custom instruction set (the stock one is used by default).
+    #[allow(dead_code)]
+    pub fn set_instruction_set(&mut self, instruction_set: InstructionSet) {
+        self.instruction_set = instruction_set;
+    }
+
+    // Number of instructions executed since the program was loaded. An input
+    // instruction that has to wait for a value is not counted until it
+    // completes.
+    #[allow(dead_code)]
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    // Start recording every value consumed and emitted from now on. Any
+    // recording already in progress is discarded.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::new());
+    }
+
+    // Stop recording and return what was captured, if anything.
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+
+    // Run the loaded program, feeding it the inputs from a recorded session,
+    // and verify that it produces the same outputs at the same steps. The
+    // simulator should be in the same state the original session started in
+    // (e.g. same pokes applied). Replaces any connected I/O channels.
+    pub fn replay(&mut self, expected: &Recording) -> Result<(), Error> {
+        let input = self.create_input_channel();
+        let output = self.create_output_channel();
+        self.set_blocking_input(false);
+        for value in expected.inputs() {
+            input.send(value)?;
+        }
+        self.start_recording();
+        self.run()?;
+        while output.try_recv().is_ok() {}
+        let actual = self.stop_recording().unwrap_or_default();
+        expected.verify(&actual)
+    }
+
     pub fn step(&mut self) -> Result<(), Error> {
+        if self.fused.is_active() && !self.strict.mode.is_enabled()
+                                  && self.instruction_set.is_stock() {
+            if let Some(fused) = self.fused.get(self.pc) {
+                return self.execute_fused(fused);
+            }
+        }
         let op = self.get_next_op()?;
+        let size = op.size();
+        if self.strict.mode.is_enabled() {
+            self.check_strict_reads(&op)?;
+        }
         let mut advance = true;
+        let mut waiting = false;
         match op {
             Op::Add{x, y, dest} => {
-                self.store(dest, self.load(x) + self.load(y))?;
+                let (x, y) = (self.load(x), self.load(y));
+                let sum = x.checked_add(y).ok_or_else(|| format_err!(
+                    "Overflow in opcode 1: {} + {}", x, y))?;
+                self.store(dest, sum)?;
             },
             Op::Multiply{x, y, dest} => {
-                self.store(dest, self.load(x) * self.load(y))?;
+                let (x, y) = (self.load(x), self.load(y));
+                let product = x.checked_mul(y).ok_or_else(|| format_err!(
+                    "Overflow in opcode 2: {} * {}", x, y))?;
+                self.store(dest, product)?;
             },
             Op::Input{dest} => {
                 match self.io.read_input()? {
-                    Some(value) => self.store(dest, value)?,
+                    Some(value) => {
+                        self.store(dest, value)?;
+                        if let Some(ref mut recording) = self.recording {
+                            recording.record_input(self.steps, value);
+                        }
+                    },
                     None => {
                         self.state = ProgramState::Wait;
                         advance = false;
+                        waiting = true;
                     },
                 }
             },
             Op::Output{value} => {
-                self.io.send_output(self.load(value))?;
+                let value = self.load(value);
+                self.io.send_output(value)?;
+                if let Some(ref mut recording) = self.recording {
+                    recording.record_output(self.steps, value);
+                }
+                self.history.record_output(value);
             },
             Op::JumpIfTrue{cond, dest} => {
                 if self.load(cond) != 0 {
-                    self.pc = self.load(dest) as usize;
+                    let target = self.load(dest) as usize;
+                    self.track_jump(target);
+                    self.pc = target;
                     advance = false;
                 }
             },
             Op::JumpIfFalse{cond, dest} => {
                 if self.load(cond) == 0 {
-                    self.pc = self.load(dest) as usize;
+                    let target = self.load(dest) as usize;
+                    self.track_jump(target);
+                    self.pc = target;
                     advance = false;
                 }
             },
@@ -214,9 +378,18 @@ impl Simulator {
             Op::Halt => {
                 self.state = ProgramState::Halted;
             },
+            Op::Custom{..} => {
+                if let Some(dest) = self.execute_custom(&op)? {
+                    self.pc = dest;
+                    advance = false;
+                }
+            },
         }
         if advance {
-            self.pc += op.size();
+            self.pc += size;
+        }
+        if !waiting {
+            self.steps += 1;
         }
         Ok(())
     }
@@ -224,7 +397,15 @@ impl Simulator {
     // Private
 
     fn get_next_op(&self) -> Result<Op, Error> {
-        let opcode = self.mem[self.pc] % 100;
+        let opcode = self.peek(self.pc) % 100;
+        if let Some(spec) = self.instruction_set.get(opcode) {
+            return Ok(Op::Custom {
+                opcode: opcode,
+                params: (0..spec.arity).map(|i| self.get_param(i))
+                                       .collect::<Result<Vec<_>, _>>()?,
+                writes: spec.writes.clone(),
+            });
+        }
         let op = match opcode {
             1 => Op::Add {
                 x: self.get_param(0)?,
@@ -266,7 +447,7 @@ impl Simulator {
             99 => Op::Halt,
             _ => {
                 return Err(format_err!("Invalid opcode: {} (pc: {})",
-                                       self.mem[self.pc], self.pc));
+                                       self.peek(self.pc), self.pc));
             },
         };
         Ok(op)
@@ -274,8 +455,14 @@ impl Simulator {
 
     // Get the ith parameter (0-based) for the current instruction.
     fn get_param(&self, i: usize) -> Result<Parameter, Error> {
-        let mode = Self::parameter_mode(self.mem[self.pc], i)?;
-        let raw_value = self.mem[self.pc + i + 1];
+        let mode = Self::parameter_mode(self.peek(self.pc), i)?;
+        let raw_value = self.peek(self.pc + i + 1);
+        self.resolve(mode, raw_value)
+    }
+
+    // Turn a raw parameter into a Parameter, given its mode.
+    fn resolve(&self, mode: ParameterMode,
+               raw_value: isize) -> Result<Parameter, Error> {
         match mode {
             ParameterMode::Position => {
                 Parameter::to_address(raw_value)
@@ -307,10 +494,11 @@ impl Simulator {
     fn store(&mut self, target: Parameter, value: isize) -> Result<(), Error> {
         match target {
             Parameter::Address(addr) => {
-                if addr >= self.mem.len() {
-                    self.mem.resize_with(addr+1, Default::default);
+                if self.strict.mode.is_enabled() {
+                    self.check_strict_write(addr)?;
                 }
-                self.mem[addr] = value;
+                self.write(addr, value);
+                self.calls.record_store(addr, value, self.steps);
                 Ok(())
             },
             Parameter::Value(_value) => {
@@ -320,6 +508,20 @@ impl Simulator {
         }
     }
 
+    fn write(&mut self, addr: usize, value: isize) {
+        if addr >= self.mem.len() {
+            self.mem.resize_with(addr+1, Default::default);
+        }
+        self.mem[addr] = value;
+        self.history.record_write(addr);
+        if self.fused.is_active() {
+            self.fused.invalidate(addr);
+        }
+        if self.strict.mode.is_enabled() {
+            self.strict.written.insert(addr);
+        }
+    }
+
     fn parameter_mode(op: isize, param: usize) -> Result<ParameterMode, Error> {
         let mut op = op as usize;
         op /= 100 * 10_usize.pow(param as u32);
@@ -336,6 +538,7 @@ impl SimulatorIO {
     fn default() -> Self {
         Self {
             input_reader: None,
+            pending_input: VecDeque::new(),
             output_sender: None,
             blocking_input: false,
         }
@@ -348,6 +551,9 @@ impl SimulatorIO {
     //      (TryRecvError happened, but we don't treat that as an error)
     //   3. An error occurred: Err(e)
     fn read_input(&mut self) -> Result<Option<isize>, Error> {
+        if let Some(value) = self.pending_input.pop_front() {
+            return Ok(Some(value));
+        }
         match self.input_reader {
             Some(ref receiver) => {
                 if self.blocking_input {
@@ -367,6 +573,13 @@ impl SimulatorIO {
         }
     }
 
+    // Move whatever is waiting on the input channel into pending_input, so it
+    // can be inspected.
+    fn buffer_input(&mut self) {
+        if let Some(ref receiver) = self.input_reader {
+            self.pending_input.extend(receiver.try_iter());
+        }
+    }
 
     fn send_output(&mut self, value: isize) -> Result<(), Error> {
         match self.output_sender {
@@ -405,6 +618,28 @@ impl Op {
             Op::Equal{..} => 4,
             Op::AdjustRelativeBase{..} => 2,
             Op::Halt => 1,
+            Op::Custom{params, ..} => params.len() + 1,
+        }
+    }
+
+    // Parameters whose values the instruction reads.
+    fn reads(&self) -> Vec<Parameter> {
+        match self {
+            Op::Add{x, y, ..} | Op::Multiply{x, y, ..} |
+            Op::LessThan{x, y, ..} | Op::Equal{x, y, ..} => vec![*x, *y],
+            Op::Input{..} | Op::Halt => vec![],
+            Op::Output{value} => vec![*value],
+            Op::JumpIfTrue{cond, dest} | Op::JumpIfFalse{cond, dest} => {
+                vec![*cond, *dest]
+            },
+            Op::AdjustRelativeBase{offset} => vec![*offset],
+            Op::Custom{params, writes, ..} => {
+                params.iter()
+                      .enumerate()
+                      .filter(|(i, _)| !writes.contains(i))
+                      .map(|(_, &p)| p)
+                      .collect()
+            },
         }
     }
 }
@@ -472,81 +707,32 @@ mod tests {
 
     #[test]
     fn test_day5_example0() {
-        match run_program_io("3,9,8,9,10,9,4,9,99,-1,8", 7) {
-            Ok(v) => assert_eq!(0, v),
-            Err(e) => assert!(false, e.to_string()),
-        }
-        match run_program_io("3,9,8,9,10,9,4,9,99,-1,8", 8) {
-            Ok(v) => assert_eq!(1, v),
-            Err(e) => assert!(false, e.to_string()),
-        }
-        match run_program_io("3,9,7,9,10,9,4,9,99,-1,8", 5) {
-            Ok(v) => assert_eq!(1, v),
-            Err(e) => assert!(false, e.to_string()),
-        }
-        match run_program_io("3,9,7,9,10,9,4,9,99,-1,8", 8) {
-            Ok(v) => assert_eq!(0, v),
-            Err(e) => assert!(false, e.to_string()),
-        }
-        match run_program_io("3,3,1108,-1,8,3,4,3,99", 7) {
-            Ok(v) => assert_eq!(0, v),
-            Err(e) => assert!(false, e.to_string()),
-        }
-        match run_program_io("3,3,1108,-1,8,3,4,3,99", 8) {
-            Ok(v) => assert_eq!(1, v),
-            Err(e) => assert!(false, e.to_string()),
-        }
-        match run_program_io("3,3,1107,-1,8,3,4,3,99", 5) {
-            Ok(v) => assert_eq!(1, v),
-            Err(e) => assert!(false, e.to_string()),
-        }
-        match run_program_io("3,3,1107,-1,8,3,4,3,99", 8) {
-            Ok(v) => assert_eq!(0, v),
-            Err(e) => assert!(false, e.to_string()),
-        }
+        assert_eq!(0, run_program_io("3,9,8,9,10,9,4,9,99,-1,8", 7).unwrap());
+        assert_eq!(1, run_program_io("3,9,8,9,10,9,4,9,99,-1,8", 8).unwrap());
+        assert_eq!(1, run_program_io("3,9,7,9,10,9,4,9,99,-1,8", 5).unwrap());
+        assert_eq!(0, run_program_io("3,9,7,9,10,9,4,9,99,-1,8", 8).unwrap());
+        assert_eq!(0, run_program_io("3,3,1108,-1,8,3,4,3,99", 7).unwrap());
+        assert_eq!(1, run_program_io("3,3,1108,-1,8,3,4,3,99", 8).unwrap());
+        assert_eq!(1, run_program_io("3,3,1107,-1,8,3,4,3,99", 5).unwrap());
+        assert_eq!(0, run_program_io("3,3,1107,-1,8,3,4,3,99", 8).unwrap());
     }
 
     #[test]
     fn test_day5_example1() {
-        match run_program_io("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", 0) {
-            Ok(v) => assert_eq!(0, v),
-            Err(e) => assert!(false, e.to_string()),
-        }
-        match run_program_io("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", 555) {
-            Ok(v) => assert_eq!(1, v),
-            Err(e) => assert!(false, e.to_string()),
-        }
+        let program = "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9";
+        assert_eq!(0, run_program_io(program, 0).unwrap());
+        assert_eq!(1, run_program_io(program, 555).unwrap());
     }
 
     #[test]
     fn test_day5_example2a() {
-        match run_program_io(
-            concat!("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,",
-                    "1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,",
-                    "999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99"),
-            5)
-        {
-            Ok(v) => assert_eq!(999, v),
-            Err(e) => assert!(false, e.to_string()),
-        }
-        match run_program_io(
-            concat!("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,",
-                    "1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,",
-                    "999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99"),
-            8)
-        {
-            Ok(v) => assert_eq!(1000, v),
-            Err(e) => assert!(false, e.to_string()),
-        }
-        match run_program_io(
-            concat!("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,",
-                    "1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,",
-                    "999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99"),
-            529)
-        {
-            Ok(v) => assert_eq!(1001, v),
-            Err(e) => assert!(false, e.to_string()),
-        }
+        let program = concat!(
+            "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,",
+            "1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,",
+            "999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99");
+        assert_eq!(999, run_program_io(program, 5).unwrap());
+        assert_eq!(1000, run_program_io(program, 8).unwrap());
+        assert_eq!(1001, run_program_io(program, 529).unwrap());
     }
 
     #[test]
@@ -629,4 +815,190 @@ mod tests {
         assert_eq!("Cannot store using immediate parameter Value(1)",
                    result.unwrap_err().to_string());
     }
+
+    #[test]
+    fn test_custom_opcodes() {
+        // Opcode 10 doubles its first parameter into its second, opcode 11
+        // outputs its parameter and jumps to 8 if it was negative.
+        let mut set = InstructionSet::stock();
+        set.register(10, "double", 2, &[1], |ctx| {
+            let value = ctx.arg(0)?;
+            ctx.set(1, value * 2)
+        }).unwrap();
+        set.register(11, "debug-print", 1, &[], |ctx| {
+            let value = ctx.arg(0)?;
+            ctx.output(value)?;
+            if value < 0 {
+                ctx.jump(8);
+            }
+            Ok(())
+        }).unwrap();
+        let program = Program::from_string("10,9,9,11,9,1111,-3,99,99,21")
+                             .unwrap();
+        let mut sim = Simulator::with_program(&program);
+        sim.set_instruction_set(set);
+        let output = sim.create_output_channel();
+        sim.run().unwrap();
+        assert_eq!(42, sim.peek(9));
+        assert_eq!(vec![42, -3], output.try_iter().collect::<Vec<isize>>());
+        assert_eq!(9, sim.pc); // Halted at 8, not 7
+
+        // Not part of the stock set
+        let mut sim = Simulator::with_program(&program);
+        assert_eq!("Invalid opcode: 10 (pc: 0)",
+                   sim.run().unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_custom_opcode_validation() {
+        let mut set = InstructionSet::stock();
+        assert!(set.register(100, "big", 0, &[], |_| Ok(())).is_err());
+        assert!(set.register(12, "bad", 1, &[1], |_| Ok(())).is_err());
+        set.register(12, "noop", 1, &[], |ctx| ctx.set(0, 1)).unwrap();
+        let program = Program::from_string("12,0,99").unwrap();
+        let mut sim = Simulator::with_program(&program);
+        sim.set_instruction_set(set);
+        assert_eq!("Parameter 0 of noop is not writable",
+                   sim.run().unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_strict_uninitialized_reads() {
+        // Adds address 100 (never written) to itself
+        let program = Program::from_string("1,100,100,5,99,-1").unwrap();
+        let mut sim = Simulator::with_program(&program);
+        sim.run().unwrap();
+        assert_eq!(0, sim.peek(5));
+
+        let mut sim = Simulator::with_program(&program);
+        sim.set_strict_mode(StrictMode::all(Strictness::Warn));
+        sim.run().unwrap();
+        assert_eq!(&vec![Violation {
+                        kind: ViolationKind::UninitializedRead,
+                        address: 100,
+                        pc: 0,
+                    }; 2],
+                   sim.warnings());
+
+        let mut sim = Simulator::with_program(&program);
+        sim.set_strict_mode(StrictMode::all(Strictness::Error));
+        assert_eq!("Read of uninitialized address 100 (pc: 0)",
+                   sim.run().unwrap_err().to_string());
+
+        // Fine once something has been written there
+        let program = Program::from_string("1101,1,2,100,1,100,100,9,99,0")
+                             .unwrap();
+        let mut sim = Simulator::with_program(&program);
+        sim.set_strict_mode(StrictMode::all(Strictness::Error));
+        sim.run().unwrap();
+        assert_eq!(6, sim.peek(9));
+    }
+
+    #[test]
+    fn test_strict_code_writes() {
+        // Overwrites the opcode of the instruction doing the writing, then
+        // writes to a data cell (fine) and to the earlier jump (not fine).
+        let program = Program::from_string(
+            "1101,1,1,0,1105,1,9,0,0,1101,7,0,7,1101,0,8,6,99").unwrap();
+        let mode = StrictMode {
+            uninitialized_reads: Strictness::Error,
+            code_writes: Strictness::Warn,
+        };
+        let mut sim = Simulator::with_program(&program);
+        sim.set_strict_mode(mode);
+        sim.run().unwrap();
+        assert_eq!(&vec![
+                       Violation {
+                           kind: ViolationKind::CodeWrite,
+                           address: 0,
+                           pc: 0,
+                       },
+                       Violation {
+                           kind: ViolationKind::CodeWrite,
+                           address: 6,
+                           pc: 13,
+                       },
+                   ],
+                   sim.warnings());
+        assert_eq!(7, sim.peek(7));
+        assert_eq!(8, sim.peek(6));
+
+        let mut sim = Simulator::with_program(&program);
+        sim.set_strict_mode(StrictMode::all(Strictness::Error));
+        assert_eq!("Write to code at address 0 (pc: 0)",
+                   sim.run().unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_running_off_the_end_fails() {
+        let program = Program::from_string("1105,1,100").unwrap();
+        let mut sim = Simulator::with_program(&program);
+        let result = sim.run();
+        assert_eq!("Invalid opcode: 0 (pc: 100)",
+                   result.unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_overflow_fails() {
+        let program = Program::from_string(
+            "1102,9223372036854775807,2,0,99").unwrap();
+        let mut sim = Simulator::with_program(&program);
+        let result = sim.run();
+        assert_eq!("Overflow in opcode 2: 9223372036854775807 * 2",
+                   result.unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_recording() {
+        let program = Program::from_string("3,9,8,9,10,9,4,9,99,-1,8").unwrap();
+        let mut sim = Simulator::with_program(&program);
+        let input = sim.create_input_channel();
+        let _output = sim.create_output_channel();
+        sim.start_recording();
+        input.send(8).unwrap();
+        sim.run().unwrap();
+        assert_eq!(4, sim.steps());
+        let recording = sim.stop_recording().unwrap();
+        assert_eq!(&vec![IoEvent::Input { step: 0, value: 8 },
+                         IoEvent::Output { step: 2, value: 1 }],
+                   recording.events());
+        assert_eq!("in 0 8\nout 2 1\n", recording.to_string());
+        assert_eq!(recording,
+                   Recording::from_string(&recording.to_string()).unwrap());
+    }
+
+    #[test]
+    fn test_replay() {
+        // Waits for input between outputs, like an interactive session.
+        let program = Program::from_string("3,11,4,11,3,11,4,11,99,0,0,0")
+                             .unwrap();
+        let mut sim = Simulator::with_program(&program);
+        let input = sim.create_input_channel();
+        let output = sim.create_output_channel();
+        sim.start_recording();
+        input.send(3).unwrap();
+        sim.run().unwrap();
+        assert_eq!(3, output.recv().unwrap());
+        input.send(4).unwrap();
+        sim.run().unwrap();
+        assert!(!sim.is_running());
+        let recording = sim.stop_recording().unwrap();
+
+        let path = std::env::temp_dir().join("advent2019_test_replay.txt");
+        let path = path.to_str().unwrap();
+        recording.save(path).unwrap();
+        let loaded = Recording::from_path(path).unwrap();
+        std::fs::remove_file(path).ok();
+
+        let mut sim = Simulator::with_program(&program);
+        assert!(sim.replay(&loaded).is_ok());
+
+        let tampered =
+            Recording::from_string("in 0 3\nout 1 3\nin 2 4\nout 3 5")
+                      .unwrap();
+        let mut sim = Simulator::with_program(&program);
+        let result = sim.replay(&tampered);
+        assert_eq!("Replay diverged at event 3: expected out 3 5, got out 3 4",
+                   result.unwrap_err().to_string());
+    }
 }
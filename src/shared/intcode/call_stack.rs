@@ -0,0 +1,204 @@
+// Reconstruction of the call stack of programs that follow the usual Intcode
+// calling convention: the caller stores the return address on the stack
+// (e.g. 21101,ret,0,0 writes it to [rb+0]) and then jumps to the function,
+// which moves the relative base past its frame with arb and eventually jumps
+// back through the stored address.
+//
+// A taken jump is treated as a call when the instruction right before it
+// stored the address following the jump. A jump to the return address of a
+// frame on the stack returns from that frame (and any frames above it).
+
+use super::Simulator;
+use anyhow::{Error, format_err};
+use std::fmt;
+
+// How many values of each frame a backtrace shows.
+const MAX_FRAME_VALUES: usize = 16;
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub struct Frame {
+    // Address of the function that was called.
+    pub entry: usize,
+    // Address of the jump that made the call.
+    pub call_site: usize,
+    pub return_address: usize,
+    // Where the return address was stored; the frame starts here.
+    pub return_slot: usize,
+}
+
+// The call stack at some point, innermost frame first, with the contents of
+// each frame.
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub struct Backtrace {
+    pub pc: usize,
+    pub frames: Vec<(Frame, Vec<isize>)>,
+}
+
+#[derive(Default)]
+pub(super) struct CallStack {
+    frames: Vec<Frame>,
+    // The most recent store: (address, value, step).
+    last_store: Option<(usize, isize, u64)>,
+}
+
+impl CallStack {
+    pub(super) fn record_store(&mut self, addr: usize, value: isize,
+                               step: u64) {
+        self.last_store = Some((addr, value, step));
+    }
+}
+
+#[allow(dead_code)]
+impl Simulator {
+    // Calls in progress, outermost first.
+    pub fn call_stack(&self) -> &Vec<Frame> {
+        &self.calls.frames
+    }
+
+    pub fn backtrace(&self) -> Backtrace {
+        let frames = &self.calls.frames;
+        let mut backtrace = Backtrace { pc: self.pc, frames: Vec::new() };
+        for (i, frame) in frames.iter().enumerate().rev() {
+            // A frame extends to where the next call stored its return
+            // address, or to the relative base for the innermost one.
+            let end = match frames.get(i + 1) {
+                Some(inner) => inner.return_slot,
+                None => self.relative_base,
+            };
+            let end = end.max(frame.return_slot + 1)
+                         .min(frame.return_slot + MAX_FRAME_VALUES);
+            let values = (frame.return_slot..end).map(|a| self.peek(a))
+                                                 .collect();
+            backtrace.frames.push((*frame, values));
+        }
+        backtrace
+    }
+
+    // Called before a taken jump from the current pc to target.
+    pub(super) fn track_jump(&mut self, target: usize) {
+        if self.track_return(target) {
+            return;
+        }
+        let calls = &mut self.calls;
+        let return_address = self.pc + 3;
+        if let Some((addr, value, step)) = calls.last_store {
+            if step + 1 == self.steps && value == return_address as isize {
+                calls.frames.push(Frame {
+                    entry: target,
+                    call_site: self.pc,
+                    return_address: return_address,
+                    return_slot: addr,
+                });
+            }
+        }
+    }
+
+    // Pop the frames a jump to target returns from. Returns whether there
+    // were any.
+    pub(super) fn track_return(&mut self, target: usize) -> bool {
+        let frames = &mut self.calls.frames;
+        match frames.iter().rposition(|f| f.return_address == target) {
+            Some(i) => {
+                frames.truncate(i);
+                true
+            },
+            None => false,
+        }
+    }
+
+    // Add the backtrace to an error raised while running, if there were any
+    // calls in progress.
+    pub(super) fn fault(&self, error: Error) -> Error {
+        if self.calls.frames.is_empty() {
+            error
+        } else {
+            format_err!("{}\nBacktrace:\n{}", error, self.backtrace())
+        }
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut pc = self.pc;
+        for (i, (frame, values)) in self.frames.iter().enumerate() {
+            let values: Vec<String> = values.iter()
+                                            .map(|v| v.to_string())
+                                            .collect();
+            writeln!(f, "#{:<3}pc {} in function {} (frame {}: {})",
+                   i, pc, frame.entry, frame.return_slot, values.join(", "))?;
+            pc = frame.call_site;
+        }
+        writeln!(f, "#{:<3}pc {} in function 0", self.frames.len(), pc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Program, ProgramState};
+
+    // Main calls f (at 10) with its return address at 100; f calls g (at 28)
+    // passing 5, then both return. The word at 30 is replaced to make g fail.
+    const PROGRAM: &str = "109,100,21101,9,0,0,1105,1,10,99,\
+                           109,3,21101,5,0,1,21101,23,0,0,1105,1,28,\
+                           109,-3,2105,1,0,\
+                           109,2,109,-2,2105,1,0";
+
+    #[test]
+    fn test_calls_and_returns() {
+        let program = Program::from_string(PROGRAM).unwrap();
+        let mut sim = Simulator::with_program(&program);
+        while sim.pc != 30 {
+            sim.step().unwrap();
+        }
+        assert_eq!(&vec![
+            Frame { entry: 10, call_site: 6, return_address: 9,
+                    return_slot: 100 },
+            Frame { entry: 28, call_site: 20, return_address: 23,
+                    return_slot: 103 },
+        ], sim.call_stack());
+        assert_eq!("#0  pc 30 in function 28 (frame 103: 23, 5)\n\
+                    #1  pc 20 in function 10 (frame 100: 9, 0, 0)\n\
+                    #2  pc 6 in function 0\n",
+                   sim.backtrace().to_string());
+
+        sim.step().unwrap();
+        sim.step().unwrap();
+        assert_eq!(23, sim.pc);
+        assert_eq!(1, sim.call_stack().len());
+        sim.run().unwrap();
+        assert_eq!(10, sim.pc); // Halted at 9
+        assert!(sim.call_stack().is_empty());
+    }
+
+    #[test]
+    fn test_fault_backtrace() {
+        let program = Program::from_string(PROGRAM).unwrap();
+        let mut sim = Simulator::with_program(&program);
+        sim.poke(30, 98);
+        assert_eq!("Invalid opcode: 98 (pc: 30)\n\
+                    Backtrace:\n\
+                    #0  pc 30 in function 28 (frame 103: 23, 5)\n\
+                    #1  pc 20 in function 10 (frame 100: 9, 0, 0)\n\
+                    #2  pc 6 in function 0\n",
+                   sim.run().unwrap_err().to_string());
+    }
+
+    #[test]
+    fn test_recursion() {
+        // Day 9 part 2 computes its answer recursively.
+        let program = Program::from_path("input/day09.txt").unwrap();
+        let mut sim = Simulator::with_program(&program);
+        let input = sim.create_input_channel();
+        let _output = sim.create_output_channel();
+        input.send(2).unwrap();
+        let mut depth = 0;
+        sim.state = ProgramState::Running;
+        while sim.is_running() {
+            sim.step().unwrap();
+            depth = depth.max(sim.call_stack().len());
+        }
+        assert!(depth > 10, "max depth {}", depth);
+        assert!(sim.call_stack().is_empty(), "{}", sim.backtrace());
+    }
+}
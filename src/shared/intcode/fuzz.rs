@@ -0,0 +1,542 @@
+// Differential testing of the Intcode VM: generate random, well-formed
+// programs plus inputs, run them on two engines side by side and report the
+// first point where their states differ. Failing cases are shrunk to a minimal
+// program before being reported.
+
+use super::{Program, ProgramState, Receiver, Simulator};
+use anyhow::Error;
+use std::fmt;
+
+// Something that can execute Intcode and expose its machine state for
+// comparison. Engines may execute several instructions per step (e.g. fused
+// instructions); states are only compared when step counts line up.
+pub trait Engine {
+    fn sim(&self) -> &Simulator;
+    fn sim_mut(&mut self) -> &mut Simulator;
+
+    fn step(&mut self) -> Result<(), Error> {
+        self.sim_mut().step()
+    }
+}
+
+impl Engine for Simulator {
+    fn sim(&self) -> &Simulator {
+        self
+    }
+
+    fn sim_mut(&mut self) -> &mut Simulator {
+        self
+    }
+}
+
+// An operand of a generated instruction. Data operands index into the data
+// region placed after the code, Rel operands are relative to a base that
+// starts at the beginning of the data region.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Operand {
+    Imm(isize),
+    Data(usize),
+    Rel(isize),
+}
+
+// A generated instruction. Jump targets are instruction indices, resolved to
+// addresses when the program is assembled.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum GenOp {
+    Add(Operand, Operand, Operand),
+    Multiply(Operand, Operand, Operand),
+    Input(Operand),
+    Output(Operand),
+    JumpIfTrue(Operand, usize),
+    JumpIfFalse(Operand, usize),
+    LessThan(Operand, Operand, Operand),
+    Equal(Operand, Operand, Operand),
+    AdjustRelativeBase(Operand),
+    Halt,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Case {
+    pub ops: Vec<GenOp>,
+    pub data: Vec<isize>,
+    pub inputs: Vec<isize>,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Divergence {
+    pub step: u64,
+    pub what: String,
+    pub a: String,
+    pub b: String,
+}
+
+#[derive(Clone,Debug)]
+pub struct Failure {
+    pub case: Case,
+    pub divergence: Divergence,
+}
+
+// Tiny xorshift PRNG so runs are reproducible from a seed.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    pub fn range(&mut self, min: isize, max: isize) -> isize {
+        min + self.below((max - min + 1) as usize) as isize
+    }
+}
+
+const MAX_OPS: usize = 24;
+const MAX_DATA: usize = 8;
+const MAX_INPUTS: usize = 6;
+
+impl Case {
+    pub fn random(rng: &mut Rng) -> Self {
+        let data_len = 1 + rng.below(MAX_DATA);
+        let num_ops = 1 + rng.below(MAX_OPS);
+        let ops = (0..num_ops).map(|_| Self::random_op(rng, num_ops, data_len))
+                              .collect();
+        Case {
+            ops: ops,
+            data: (0..data_len).map(|_| rng.range(-10, 10)).collect(),
+            inputs: (0..rng.below(MAX_INPUTS+1)).map(|_| rng.range(-10, 10))
+                                                .collect(),
+        }
+    }
+
+    fn random_op(rng: &mut Rng, num_ops: usize, data_len: usize) -> GenOp {
+        let read = |rng: &mut Rng| match rng.below(3) {
+            0 => Operand::Imm(rng.range(-10, 10)),
+            1 => Operand::Data(rng.below(data_len)),
+            _ => Operand::Rel(rng.below(data_len) as isize),
+        };
+        let write = |rng: &mut Rng| match rng.below(2) {
+            0 => Operand::Data(rng.below(data_len)),
+            _ => Operand::Rel(rng.below(data_len) as isize),
+        };
+        match rng.below(10) {
+            0 => GenOp::Add(read(rng), read(rng), write(rng)),
+            1 => GenOp::Multiply(read(rng), read(rng), write(rng)),
+            2 => GenOp::Input(write(rng)),
+            3 => GenOp::Output(read(rng)),
+            4 => GenOp::JumpIfTrue(read(rng), rng.below(num_ops+1)),
+            5 => GenOp::JumpIfFalse(read(rng), rng.below(num_ops+1)),
+            6 => GenOp::LessThan(read(rng), read(rng), write(rng)),
+            7 => GenOp::Equal(read(rng), read(rng), write(rng)),
+            8 => GenOp::AdjustRelativeBase(Operand::Imm(rng.range(-2, 2))),
+            _ => GenOp::Halt,
+        }
+    }
+
+    // Lay out the program: "109,<data start>" to point the relative base at
+    // the data, then the generated ops, a final halt, then the data region.
+    pub fn assemble(&self) -> Program {
+        let mut addresses = Vec::new();
+        let mut addr = 2;
+        for op in self.ops.iter() {
+            addresses.push(addr);
+            addr += op.size();
+        }
+        addresses.push(addr); // the final halt
+        let data_start = addr as isize + 1;
+
+        let mut mem = vec![109, data_start];
+        let mut emit = |opcode: isize, params: &[Operand]| {
+            let mut inst = opcode;
+            let mut raw = Vec::new();
+            for (i, &param) in params.iter().enumerate() {
+                let (mode, value) = match param {
+                    Operand::Imm(v) => (1, v),
+                    Operand::Data(d) => (0, data_start + d as isize),
+                    Operand::Rel(offset) => (2, offset),
+                };
+                inst += mode * 100 * 10_isize.pow(i as u32);
+                raw.push(value);
+            }
+            mem.push(inst);
+            mem.extend(raw);
+        };
+        for op in self.ops.iter() {
+            let target = |t: usize| Operand::Imm(addresses[t] as isize);
+            match *op {
+                GenOp::Add(x, y, d) => emit(1, &[x, y, d]),
+                GenOp::Multiply(x, y, d) => emit(2, &[x, y, d]),
+                GenOp::Input(d) => emit(3, &[d]),
+                GenOp::Output(v) => emit(4, &[v]),
+                GenOp::JumpIfTrue(c, t) => emit(5, &[c, target(t)]),
+                GenOp::JumpIfFalse(c, t) => emit(6, &[c, target(t)]),
+                GenOp::LessThan(x, y, d) => emit(7, &[x, y, d]),
+                GenOp::Equal(x, y, d) => emit(8, &[x, y, d]),
+                GenOp::AdjustRelativeBase(o) => emit(9, &[o]),
+                GenOp::Halt => emit(99, &[]),
+            }
+        }
+        mem.push(99);
+        mem.extend(self.data.iter());
+        Program { instructions: mem }
+    }
+
+    // Candidate simplifications of this case, roughly smallest first.
+    fn shrink_candidates(&self) -> Vec<Case> {
+        let mut result = Vec::new();
+        for i in 0..self.ops.len() {
+            let mut case = self.clone();
+            case.ops.remove(i);
+            for op in case.ops.iter_mut() {
+                op.retarget(|t| if t > i { t - 1 } else { t });
+            }
+            result.push(case);
+        }
+        for i in 0..self.inputs.len() {
+            let mut case = self.clone();
+            case.inputs.remove(i);
+            result.push(case);
+        }
+        if self.data.len() > 1 {
+            let mut case = self.clone();
+            case.data.pop();
+            if case.ops.iter().all(|op| op.fits_data(case.data.len())) {
+                result.push(case);
+            }
+        }
+        for i in 0..self.data.len() {
+            if self.data[i] != 0 {
+                let mut case = self.clone();
+                case.data[i] = 0;
+                result.push(case);
+            }
+        }
+        for i in 0..self.inputs.len() {
+            if self.inputs[i] != 0 {
+                let mut case = self.clone();
+                case.inputs[i] = 0;
+                result.push(case);
+            }
+        }
+        result
+    }
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let program = self.assemble();
+        write!(f, "program: {}\ninputs: {}",
+               join(&program.instructions), join(&self.inputs))
+    }
+}
+
+impl GenOp {
+    fn size(&self) -> usize {
+        match self {
+            GenOp::Add(..) | GenOp::Multiply(..) |
+            GenOp::LessThan(..) | GenOp::Equal(..) => 4,
+            GenOp::JumpIfTrue(..) | GenOp::JumpIfFalse(..) => 3,
+            GenOp::Input(..) | GenOp::Output(..) |
+            GenOp::AdjustRelativeBase(..) => 2,
+            GenOp::Halt => 1,
+        }
+    }
+
+    fn retarget<F: Fn(usize) -> usize>(&mut self, f: F) {
+        match self {
+            GenOp::JumpIfTrue(_, t) | GenOp::JumpIfFalse(_, t) => *t = f(*t),
+            _ => {},
+        }
+    }
+
+    fn operands(&self) -> Vec<Operand> {
+        match *self {
+            GenOp::Add(x, y, d) | GenOp::Multiply(x, y, d) |
+            GenOp::LessThan(x, y, d) | GenOp::Equal(x, y, d) => vec![x, y, d],
+            GenOp::Input(o) | GenOp::Output(o) |
+            GenOp::AdjustRelativeBase(o) => vec![o],
+            GenOp::JumpIfTrue(c, _) | GenOp::JumpIfFalse(c, _) => vec![c],
+            GenOp::Halt => vec![],
+        }
+    }
+
+    fn fits_data(&self, data_len: usize) -> bool {
+        self.operands().iter().all(|o| match *o {
+            Operand::Data(d) => d < data_len,
+            Operand::Rel(offset) => offset < data_len as isize,
+            Operand::Imm(_) => true,
+        })
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Divergence at step {}: {} differs ({} vs {})",
+               self.step, self.what, self.a, self.b)
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n{}", self.divergence, self.case)
+    }
+}
+
+// An engine under test along with the channel its outputs arrive on.
+struct Harnessed<E: Engine> {
+    engine: E,
+    _output: Receiver,
+    error: Option<String>,
+}
+
+impl<E: Engine> Harnessed<E> {
+    fn new(mut engine: E, inputs: &[isize]) -> Self {
+        let sim = engine.sim_mut();
+        let input = sim.create_input_channel();
+        let output = sim.create_output_channel();
+        sim.set_blocking_input(false);
+        sim.start_recording();
+        sim.state = ProgramState::Running;
+        for &value in inputs {
+            input.send(value).unwrap();
+        }
+        Harnessed { engine: engine, _output: output, error: None }
+    }
+
+    fn sim(&self) -> &Simulator {
+        self.engine.sim()
+    }
+
+    // True once the engine can't make any further progress.
+    fn is_stopped(&self) -> bool {
+        self.error.is_some() || self.sim().state != ProgramState::Running
+    }
+
+    fn step(&mut self) {
+        if let Err(e) = self.engine.step() {
+            self.error = Some(e.to_string());
+        }
+    }
+}
+
+// Run a case on two engines in lockstep for at most max_steps instructions
+// and return the first divergence in state, if any.
+pub fn run_differential<A, B, EA, EB>(case: &Case, make_a: &A, make_b: &B,
+                                      max_steps: u64) -> Option<Divergence>
+    where A: Fn(&Program) -> EA, B: Fn(&Program) -> EB,
+          EA: Engine, EB: Engine
+{
+    let program = case.assemble();
+    compare_engines(make_a(&program), make_b(&program), &case.inputs, max_steps)
+}
+
+// Run two engines (already loaded with the same program) in lockstep, feeding
+// both the same inputs.
+pub fn compare_engines<EA, EB>(a: EA, b: EB, inputs: &[isize],
+                               max_steps: u64) -> Option<Divergence>
+    where EA: Engine, EB: Engine
+{
+    let mut a = Harnessed::new(a, inputs);
+    let mut b = Harnessed::new(b, inputs);
+    loop {
+        let (a_steps, b_steps) = (a.sim().steps, b.sim().steps);
+        let a_behind = !a.is_stopped()
+                       && (a_steps <= b_steps || b.is_stopped());
+        let b_behind = !b.is_stopped()
+                       && (b_steps <= a_steps || a.is_stopped());
+        if a_behind {
+            a.step();
+        }
+        if b_behind {
+            b.step();
+        }
+        let (a_steps, b_steps) = (a.sim().steps, b.sim().steps);
+        if a_steps == b_steps || (a_steps < b_steps && a.is_stopped())
+                              || (b_steps < a_steps && b.is_stopped()) {
+            if let Some(divergence) = compare_states(&a, &b) {
+                return Some(divergence);
+            }
+        }
+        let stuck = !a_behind && !b_behind;
+        if stuck || a_steps.max(b_steps) >= max_steps {
+            return None;
+        }
+    }
+}
+
+fn compare_states<EA: Engine, EB: Engine>(
+    a: &Harnessed<EA>, b: &Harnessed<EB>) -> Option<Divergence>
+{
+    let (sa, sb) = (a.sim(), b.sim());
+    let step = sa.steps.min(sb.steps);
+    let diverged = |what: &str, x: String, y: String| {
+        Some(Divergence { step: step, what: what.to_string(), a: x, b: y })
+    };
+    if a.error != b.error {
+        return diverged("error", format!("{:?}", a.error),
+                        format!("{:?}", b.error));
+    }
+    if sa.steps != sb.steps {
+        return diverged("step count", sa.steps.to_string(),
+                        sb.steps.to_string());
+    }
+    if sa.state != sb.state {
+        return diverged("state", format!("{:?}", sa.state),
+                        format!("{:?}", sb.state));
+    }
+    if sa.pc != sb.pc {
+        return diverged("pc", sa.pc.to_string(), sb.pc.to_string());
+    }
+    if sa.relative_base != sb.relative_base {
+        return diverged("relative base", sa.relative_base.to_string(),
+                        sb.relative_base.to_string());
+    }
+    let len = sa.mem.len().max(sb.mem.len());
+    if sa.mem != sb.mem {
+        for addr in 0..len {
+            if sa.peek(addr) != sb.peek(addr) {
+                return diverged(&format!("mem[{}]", addr),
+                                sa.peek(addr).to_string(),
+                                sb.peek(addr).to_string());
+            }
+        }
+    }
+    if sa.call_stack() != sb.call_stack() {
+        return diverged("call stack", sa.backtrace().to_string(),
+                        sb.backtrace().to_string());
+    }
+    if sa.recording != sb.recording {
+        let events = |sim: &Simulator| {
+            sim.recording.as_ref()
+               .map(|r| r.events().iter().map(|e| e.to_string())
+                                         .collect::<Vec<String>>()
+                                         .join(", "))
+               .unwrap_or_default()
+        };
+        return diverged("I/O", events(sa), events(sb));
+    }
+    None
+}
+
+// Greedily apply simplifications while the case still diverges.
+pub fn shrink<A, B, EA, EB>(case: &Case, make_a: &A, make_b: &B,
+                            max_steps: u64) -> Failure
+    where A: Fn(&Program) -> EA, B: Fn(&Program) -> EB,
+          EA: Engine, EB: Engine
+{
+    let mut case = case.clone();
+    let mut divergence = run_differential(&case, make_a, make_b, max_steps)
+                             .expect("shrink called on a passing case");
+    'outer: loop {
+        for candidate in case.shrink_candidates() {
+            if let Some(d) = run_differential(&candidate, make_a, make_b,
+                                              max_steps) {
+                case = candidate;
+                divergence = d;
+                continue 'outer;
+            }
+        }
+        break;
+    }
+    Failure { case: case, divergence: divergence }
+}
+
+// Generate and run the given number of random cases. Returns the first
+// failure found, shrunk to a minimal case.
+pub fn fuzz<A, B, EA, EB>(seed: u64, iterations: usize, make_a: &A, make_b: &B,
+                          max_steps: u64) -> Option<Failure>
+    where A: Fn(&Program) -> EA, B: Fn(&Program) -> EB,
+          EA: Engine, EB: Engine
+{
+    let mut rng = Rng::new(seed);
+    for _ in 0..iterations {
+        let case = Case::random(&mut rng);
+        if run_differential(&case, make_a, make_b, max_steps).is_some() {
+            return Some(shrink(&case, make_a, make_b, max_steps));
+        }
+    }
+    None
+}
+
+fn join(values: &[isize]) -> String {
+    values.iter()
+          .map(|v| v.to_string())
+          .collect::<Vec<String>>()
+          .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_STEPS: u64 = 1000;
+
+    // A deliberately broken engine: LessThan stores the wrong answer.
+    struct BrokenLessThan(Simulator);
+
+    impl Engine for BrokenLessThan {
+        fn sim(&self) -> &Simulator {
+            &self.0
+        }
+
+        fn sim_mut(&mut self) -> &mut Simulator {
+            &mut self.0
+        }
+
+        fn step(&mut self) -> Result<(), Error> {
+            let dest = match self.0.peek(self.0.pc) % 100 {
+                7 => Some(self.0.get_param(2)?),
+                _ => None,
+            };
+            self.0.step()?;
+            if let Some(dest) = dest {
+                let value = self.0.load(dest);
+                self.0.store(dest, 1 - value)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_assemble() {
+        let case = Case {
+            ops: vec![GenOp::Input(Operand::Data(0)),
+                      GenOp::JumpIfTrue(Operand::Rel(0), 3),
+                      GenOp::Output(Operand::Imm(7)),
+                      GenOp::Output(Operand::Data(0))],
+            data: vec![0],
+            inputs: vec![5],
+        };
+        assert_eq!(vec![109, 12, 3, 12, 1205, 0, 9, 104, 7, 4, 12, 99, 0],
+                   case.assemble().instructions[..13].to_vec());
+    }
+
+    #[test]
+    fn test_identical_engines_agree() {
+        let make = |p: &Program| Simulator::with_program(p);
+        let failure = fuzz(1, 500, &make, &make, MAX_STEPS);
+        assert!(failure.is_none(), "{}", failure.unwrap());
+    }
+
+    #[test]
+    fn test_finds_and_shrinks_divergence() {
+        let make_a = |p: &Program| Simulator::with_program(p);
+        let make_b = |p: &Program| BrokenLessThan(Simulator::with_program(p));
+        let failure = fuzz(1, 500, &make_a, &make_b, MAX_STEPS)
+                          .expect("should find the broken LessThan");
+        assert!(failure.divergence.what.starts_with("mem["),
+                "{}", failure);
+        assert_eq!(1, failure.case.ops.len(), "{}", failure);
+        assert!(matches!(failure.case.ops[0], GenOp::LessThan(..)),
+                "{}", failure);
+    }
+}
@@ -0,0 +1,239 @@
+// Runner for the golden test files under tests/intcode. See basics.test there
+// for the format. Every case in every file is run and all mismatches are
+// reported together.
+
+use super::{Program, ProgramState, Simulator};
+use anyhow::{Error, format_err};
+use std::fs;
+use std::path::Path;
+
+const DIR: &str = "tests/intcode";
+const MAX_STEPS: u64 = 1_000_000;
+
+#[derive(Clone,Debug,Default)]
+struct Case {
+    name: String,
+    // Where the case starts, as "file:line".
+    location: String,
+    program: String,
+    input: Vec<isize>,
+    output: Option<Vec<isize>>,
+    memory: Vec<(usize, isize)>,
+    error: Option<String>,
+}
+
+fn load_file(path: &Path) -> Result<Vec<Case>, Error> {
+    let text = fs::read_to_string(path)?;
+    let mut cases: Vec<Case> = Vec::new();
+    let mut entries: Vec<(usize, String, String)> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if line.starts_with(char::is_whitespace) {
+            match entries.last_mut() {
+                Some(entry) => entry.2.push_str(trimmed),
+                None => return Err(format_err!(
+                    "{}:{}: continuation without a key", path.display(), i+1)),
+            }
+            continue;
+        }
+        match trimmed.find(':') {
+            Some(pos) => entries.push((i + 1,
+                                       trimmed[..pos].trim().to_string(),
+                                       trimmed[pos+1..].trim().to_string())),
+            None => return Err(format_err!(
+                "{}:{}: expected 'key: value'", path.display(), i+1)),
+        }
+    }
+    for (line, key, value) in entries {
+        let error = |e: Error| {
+            format_err!("{}:{}: {}", path.display(), line, e)
+        };
+        if key == "test" {
+            let program = cases.last().map(|c| c.program.clone())
+                                      .unwrap_or_default();
+            cases.push(Case {
+                name: value,
+                location: format!("{}:{}", path.display(), line),
+                program: program,
+                ..Case::default()
+            });
+            continue;
+        }
+        let case = cases.last_mut().ok_or_else(|| format_err!(
+            "{}:{}: '{}' before the first test", path.display(), line, key))?;
+        match key.as_str() {
+            "program" => case.program = value,
+            "input" => case.input = parse_list(&value).map_err(error)?,
+            "output" => {
+                case.output = Some(parse_list(&value).map_err(error)?);
+            },
+            "memory" => case.memory = parse_memory(&value).map_err(error)?,
+            "error" => case.error = Some(value),
+            _ => return Err(error(format_err!("Unknown key '{}'", key))),
+        }
+    }
+    Ok(cases)
+}
+
+fn parse_list(text: &str) -> Result<Vec<isize>, Error> {
+    text.split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<isize>().map_err(|e| format_err!("{}: {}", s, e)))
+        .collect()
+}
+
+fn parse_memory(text: &str) -> Result<Vec<(usize, isize)>, Error> {
+    text.split(',').map(|pair| {
+        let mut parts = pair.split('=');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(addr), Some(value), None) => {
+                Ok((addr.trim().parse()?, value.trim().parse()?))
+            },
+            _ => Err(format_err!("Expected addr=value, got '{}'", pair)),
+        }
+    }).collect()
+}
+
+// The kind of error a message describes, as named in the test files.
+fn error_kind(message: &str) -> &'static str {
+    let kinds = [
+        ("Invalid opcode", "invalid-opcode"),
+        ("Invalid address", "invalid-address"),
+        ("Invalid parameter mode", "invalid-mode"),
+        ("Cannot store using immediate", "immediate-store"),
+        ("Overflow", "overflow"),
+    ];
+    kinds.iter()
+         .find(|(prefix, _)| message.starts_with(prefix))
+         .map(|&(_, kind)| kind)
+         .unwrap_or("other")
+}
+
+// Run a case and describe everything that didn't match.
+fn run_case(case: &Case) -> Vec<String> {
+    let program = match Program::from_string(&case.program) {
+        Ok(program) => program,
+        Err(e) => return vec![format!("bad program: {}", e)],
+    };
+    let mut sim = Simulator::with_program(&program);
+    let input = sim.create_input_channel();
+    let output = sim.create_output_channel();
+    for &value in case.input.iter() {
+        input.send(value).unwrap();
+    }
+    sim.state = ProgramState::Running;
+    let mut error = None;
+    while sim.state == ProgramState::Running && sim.steps < MAX_STEPS {
+        if let Err(e) = sim.step() {
+            error = Some(e.to_string());
+            break;
+        }
+    }
+    let actual_error = match (&error, &sim.state) {
+        (Some(message), _) => Some(error_kind(message).to_string()),
+        (None, ProgramState::Wait) => Some("needs-input".to_string()),
+        (None, ProgramState::Running) => Some(format!(
+            "did not halt within {} steps", MAX_STEPS)),
+        (None, ProgramState::Halted) => None,
+    };
+
+    let mut mismatches = Vec::new();
+    if actual_error != case.error {
+        mismatches.push(format!(
+            "error\n    expected: {}\n      actual: {}",
+            case.error.as_deref().unwrap_or("none"),
+            match (&actual_error, &error) {
+                (Some(kind), Some(message)) => {
+                    format!("{} ({})", kind, message)
+                },
+                (Some(kind), None) => kind.clone(),
+                (None, _) => "none".to_string(),
+            }));
+    }
+    if let Some(ref expected) = case.output {
+        let actual: Vec<isize> = output.try_iter().collect();
+        if let Some(diff) = diff(expected, &actual) {
+            mismatches.push(format!("output\n{}", diff));
+        }
+    }
+    for &(addr, value) in case.memory.iter() {
+        if sim.peek(addr) != value {
+            mismatches.push(format!(
+                "mem[{}]\n    expected: {}\n      actual: {}",
+                addr, value, sim.peek(addr)));
+        }
+    }
+    mismatches
+}
+
+// Both lists, with the first difference pointed out.
+fn diff(expected: &[isize], actual: &[isize]) -> Option<String> {
+    if expected == actual {
+        return None;
+    }
+    let index = expected.iter()
+                        .zip(actual.iter())
+                        .position(|(a, b)| a != b)
+                        .unwrap_or(expected.len().min(actual.len()));
+    let join = |values: &[isize]| {
+        values.iter()
+              .map(|v| v.to_string())
+              .collect::<Vec<String>>()
+              .join(",")
+    };
+    // Both lists agree up to index, so the caret lines up under each.
+    let offset = join(&expected[..index]).len() + (index > 0) as usize;
+    Some(format!("    expected: {}\n      actual: {}\n{}^ index {}",
+                 join(expected), join(actual), " ".repeat(14 + offset), index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_golden_files() {
+        let mut paths: Vec<_> = fs::read_dir(DIR)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.extension().map(|e| e == "test").unwrap_or(false))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty(), "no golden files in {}", DIR);
+        let mut report = Vec::new();
+        let mut count = 0;
+        for path in paths {
+            for case in load_file(&path).unwrap().iter() {
+                count += 1;
+                for mismatch in run_case(case) {
+                    report.push(format!("{} ({}): {}", case.location,
+                                        case.name, mismatch));
+                }
+            }
+        }
+        assert!(report.is_empty(), "{} mismatches in {} cases:\n\n{}\n",
+                report.len(), count, report.join("\n\n"));
+    }
+
+    #[test]
+    fn test_mismatches_reported() {
+        let case = Case {
+            name: "broken".to_string(),
+            program: "104,1,104,2,104,3,99".to_string(),
+            output: Some(vec![1, 5, 3]),
+            memory: vec![(0, 4)],
+            error: Some("overflow".to_string()),
+            ..Case::default()
+        };
+        assert_eq!(vec![
+            "error\n    expected: overflow\n      actual: none".to_string(),
+            format!("output\n    expected: 1,5,3\n      actual: 1,2,3\n\
+                     {}^ index 1", " ".repeat(16)),
+            "mem[0]\n    expected: 4\n      actual: 104".to_string(),
+        ], run_case(&case));
+    }
+}
@@ -0,0 +1,283 @@
+// Views of a Simulator's state: a hex-dump style table of memory, and a live
+// terminal view (like `top`) showing the code around pc, the stack frame at
+// the relative base, the most recent writes and the I/O queues.
+
+use super::{ProgramState, Simulator};
+use anyhow::{Error, format_err};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::thread;
+use std::time;
+
+const ROW_SIZE: usize = 8;
+
+const RESET: &str = "\x1B[0m";
+const PC: &str = "\x1B[7m";
+const OPERANDS: &str = "\x1B[36m";
+const BASE: &str = "\x1B[32;1m";
+const WRITTEN: &str = "\x1B[33;1m";
+const CLEAR_LINE: &str = "\x1B[K";
+
+// The last few addresses written and values output. Nothing is kept unless a
+// capacity is set.
+#[derive(Default)]
+pub(super) struct History {
+    capacity: usize,
+    writes: VecDeque<usize>,
+    outputs: VecDeque<isize>,
+}
+
+// Runs a Simulator while redrawing the live view every so many steps.
+pub struct Inspector {
+    interval: u64,
+    delay: time::Duration,
+    started: bool,
+}
+
+impl History {
+    pub(super) fn clear(&mut self) {
+        self.writes.clear();
+        self.outputs.clear();
+    }
+
+    pub(super) fn record_write(&mut self, addr: usize) {
+        if self.capacity > 0 {
+            self.writes.retain(|&a| a != addr);
+            Self::push(&mut self.writes, addr, self.capacity);
+        }
+    }
+
+    pub(super) fn record_output(&mut self, value: isize) {
+        if self.capacity > 0 {
+            Self::push(&mut self.outputs, value, self.capacity);
+        }
+    }
+
+    fn push<T>(queue: &mut VecDeque<T>, value: T, capacity: usize) {
+        queue.push_back(value);
+        while queue.len() > capacity {
+            queue.pop_front();
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Simulator {
+    // Remember the last n addresses written and values output, for the live
+    // view. Zero turns it off.
+    pub fn track_history(&mut self, n: usize) {
+        self.history.capacity = n;
+        self.history.clear();
+    }
+
+    // Addresses written most recently, oldest first.
+    pub fn recent_writes(&self) -> &VecDeque<usize> {
+        &self.history.writes
+    }
+
+    // Input values queued up but not consumed by the program yet.
+    pub fn pending_input(&mut self) -> &VecDeque<isize> {
+        self.io.buffer_input();
+        &self.io.pending_input
+    }
+
+    // Memory as a table with ROW_SIZE values per row, covering whole rows.
+    pub fn dump(&self, range: Range<usize>) -> String {
+        self.table(range, &|_| None)
+    }
+
+    // One frame of the live view. Each line ends by clearing the rest of the
+    // terminal line, so frames can be drawn over each other.
+    pub fn live_view(&self) -> String {
+        let size = self.get_next_op().map(|op| op.size()).unwrap_or(1);
+        let (pc, rb) = (self.pc, self.relative_base);
+        let writes = &self.history.writes;
+        let highlight = |addr: usize| {
+            if addr == pc {
+                Some(PC)
+            } else if addr > pc && addr < pc + size {
+                Some(OPERANDS)
+            } else if writes.contains(&addr) {
+                Some(WRITTEN)
+            } else if addr == rb {
+                Some(BASE)
+            } else {
+                None
+            }
+        };
+        let around = |addr: usize, before: usize, after: usize| {
+            let row = addr / ROW_SIZE;
+            row.saturating_sub(before) * ROW_SIZE..(row + after + 1) * ROW_SIZE
+        };
+        let mut lines = vec![
+            format!("pc: {}  rb: {}  steps: {}  state: {:?}",
+                    pc, rb, self.steps, self.state),
+            String::new(),
+            format!("{}code{}", OPERANDS, RESET),
+        ];
+        lines.extend(self.table(around(pc, 2, 3), &highlight).lines()
+                         .map(|l| l.to_string()));
+        lines.push(String::new());
+        lines.push(format!("{}stack frame{}", BASE, RESET));
+        lines.extend(self.table(around(rb, 2, 3), &highlight).lines()
+                         .map(|l| l.to_string()));
+        lines.push(String::new());
+        lines.push(format!("call stack: {}",
+                           join(self.calls_innermost_first())));
+        lines.push(format!("{}recent writes:{} {}", WRITTEN, RESET,
+                           join(writes.iter().rev())));
+        lines.push(format!("input queue: {}",
+                           join(self.io.pending_input.iter())));
+        lines.push(format!("recent output: {}",
+                           join(self.history.outputs.iter())));
+        lines.iter()
+             .map(|l| format!("{}{}\n", l, CLEAR_LINE))
+             .collect()
+    }
+
+    // Entry points of the functions being run, innermost first.
+    fn calls_innermost_first(&self) -> impl Iterator<Item=usize> + '_ {
+        self.call_stack().iter()
+                         .rev()
+                         .map(|f| f.entry)
+                         .chain(std::iter::once(0))
+    }
+
+    fn table(&self, range: Range<usize>,
+             highlight: &dyn Fn(usize) -> Option<&'static str>) -> String {
+        let start = range.start / ROW_SIZE * ROW_SIZE;
+        let end = range.end.div_ceil(ROW_SIZE) * ROW_SIZE;
+        let width = (start..end).map(|a| self.peek(a).to_string().len())
+                                .max()
+                                .unwrap_or(1)
+                                .max(4);
+        let mut table = format!("{:>6} |", "addr");
+        for i in 0..ROW_SIZE {
+            table.push_str(&format!(" {:>width$}", format!("+{}", i),
+                                    width=width));
+        }
+        table.push('\n');
+        for row in (start..end).step_by(ROW_SIZE) {
+            table.push_str(&format!("{:>6} |", row));
+            for addr in row..row+ROW_SIZE {
+                let value = format!("{:>width$}", self.peek(addr),
+                                    width=width);
+                match highlight(addr) {
+                    Some(color) => {
+                        table.push_str(&format!(" {}{}{}",
+                                                color, value, RESET));
+                    },
+                    None => table.push_str(&format!(" {}", value)),
+                }
+            }
+            table.push('\n');
+        }
+        table
+    }
+}
+
+impl Inspector {
+    // Redraw every `interval` steps, pausing for `delay` after each frame.
+    pub fn new(interval: u64, delay: time::Duration) -> Self {
+        Self {
+            interval: interval.max(1),
+            delay: delay,
+            started: false,
+        }
+    }
+
+    // Use in place of Simulator::run. The screen is cleared the first time;
+    // call finish() (or drop the Inspector) to restore the cursor afterwards.
+    // Errors come with the memory around pc.
+    pub fn run(&mut self, sim: &mut Simulator) -> Result<(), Error> {
+        if !self.started {
+            print!("\x1B[2J\x1B[?25l"); // Clear screen, hide cursor
+            sim.track_history(16);
+            self.started = true;
+        }
+        sim.state = ProgramState::Running;
+        let result = loop {
+            if let Err(e) = sim.step() {
+                let row = sim.pc / ROW_SIZE * ROW_SIZE;
+                let around = row.saturating_sub(2 * ROW_SIZE)..row + ROW_SIZE;
+                break Err(format_err!("{}\n{}", e, sim.dump(around)));
+            }
+            if sim.steps.is_multiple_of(self.interval) {
+                self.draw(sim);
+            }
+            if sim.state != ProgramState::Running {
+                break Ok(());
+            }
+        };
+        self.draw(sim);
+        result
+    }
+
+    pub fn finish(&mut self) {
+        if self.started {
+            print!("\x1B[?25h"); // Show cursor
+            self.started = false;
+        }
+    }
+
+    fn draw(&self, sim: &mut Simulator) {
+        sim.io.buffer_input();
+        print!("\x1B[H{}", sim.live_view()); // Cursor to top left first
+        io::stdout().flush().unwrap_or(());
+        thread::sleep(self.delay);
+    }
+}
+
+impl Drop for Inspector {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+fn join<T: ToString>(values: impl Iterator<Item=T>) -> String {
+    values.map(|v| v.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Program;
+
+    #[test]
+    fn test_dump() {
+        let program = Program::from_string("1,9,10,11,99,0,0,0,0,30,-4")
+                              .unwrap();
+        let sim = Simulator::with_program(&program);
+        assert_eq!("  addr |   +0   +1   +2   +3   +4   +5   +6   +7\n\
+                    \x20    0 |    1    9   10   11   99    0    0    0\n\
+                    \x20    8 |    0   30   -4    0    0    0    0    0\n",
+                   sim.dump(3..11));
+    }
+
+    #[test]
+    fn test_live_view() {
+        let program = Program::from_string("1101,2,3,20,3,21,99").unwrap();
+        let mut sim = Simulator::with_program(&program);
+        sim.track_history(4);
+        let input = sim.create_input_channel();
+        input.send(7).unwrap();
+        input.send(8).unwrap();
+        sim.step().unwrap();
+        assert_eq!(&VecDeque::from(vec![20]), sim.recent_writes());
+        assert_eq!(&VecDeque::from(vec![7, 8]), sim.pending_input());
+
+        let view = sim.live_view();
+        assert!(view.starts_with(
+            "pc: 4  rb: 0  steps: 1  state: Halted\x1B[K\n"), "{}", view);
+        assert!(view.contains(&format!("{}   3{}", PC, RESET)));
+        assert!(view.contains(&format!("{}  21{}", OPERANDS, RESET)));
+        assert!(view.contains(&format!("{}   5{}", WRITTEN, RESET)));
+        assert!(view.contains("recent writes:\x1B[0m 20\x1B[K\n"));
+        assert!(view.contains("input queue: 7, 8\x1B[K\n"));
+
+        sim.run().unwrap();
+        assert_eq!(7, sim.peek(21));
+        assert_eq!(&VecDeque::from(vec![8]), sim.pending_input());
+    }
+}
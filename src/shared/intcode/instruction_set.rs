@@ -0,0 +1,151 @@
+// Custom opcodes, for experimenting with Intcode dialects. The stock
+// instructions (1-9 and 99) are built into the Simulator; an InstructionSet
+// adds to or overrides them.
+
+use super::{Op, Parameter, ProgramState, Simulator};
+use anyhow::{Error, format_err};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub type OpCallback = dyn Fn(&mut OpContext) -> Result<(), Error> + Send + Sync;
+
+pub struct OpSpec {
+    pub name: String,
+    pub arity: usize,
+    // Indices of the parameters that are written to (like the dest of Add).
+    pub writes: Vec<usize>,
+    callback: Box<OpCallback>,
+}
+
+#[derive(Clone,Default)]
+pub struct InstructionSet {
+    custom: HashMap<isize, Arc<OpSpec>>,
+}
+
+// What an OpCallback gets to work with: the instruction's parameters and the
+// machine executing it.
+pub struct OpContext<'a> {
+    sim: &'a mut Simulator,
+    spec: &'a OpSpec,
+    params: &'a [Parameter],
+    jump: Option<usize>,
+}
+
+#[allow(dead_code)]
+impl InstructionSet {
+    // The stock instruction set, with nothing added.
+    pub fn stock() -> Self {
+        Self::default()
+    }
+
+    // Register an opcode (0-99). Registering a stock opcode replaces it.
+    pub fn register<F>(&mut self, opcode: isize, name: &str, arity: usize,
+                       writes: &[usize], callback: F) -> Result<(), Error>
+        where F: Fn(&mut OpContext) -> Result<(), Error> + Send + Sync + 'static
+    {
+        if !(0..=99).contains(&opcode) {
+            return Err(format_err!("Opcode out of range: {}", opcode));
+        }
+        if let Some(&i) = writes.iter().find(|&&i| i >= arity) {
+            return Err(format_err!(
+                "Write parameter {} out of range for {} (arity {})",
+                i, name, arity));
+        }
+        self.custom.insert(opcode, Arc::new(OpSpec {
+            name: name.to_string(),
+            arity: arity,
+            writes: writes.to_vec(),
+            callback: Box::new(callback),
+        }));
+        Ok(())
+    }
+
+    pub fn get(&self, opcode: isize) -> Option<&Arc<OpSpec>> {
+        self.custom.get(&opcode)
+    }
+
+    // True if nothing has been added or overridden.
+    pub fn is_stock(&self) -> bool {
+        self.custom.is_empty()
+    }
+}
+
+#[allow(dead_code)]
+impl<'a> OpContext<'a> {
+    // Value of the ith parameter (dereferenced if it is an address).
+    pub fn arg(&self, i: usize) -> Result<isize, Error> {
+        Ok(self.sim.load(self.param(i)?))
+    }
+
+    // Store into the ith parameter, which must be a write parameter.
+    pub fn set(&mut self, i: usize, value: isize) -> Result<(), Error> {
+        if !self.spec.writes.contains(&i) {
+            return Err(format_err!("Parameter {} of {} is not writable",
+                                   i, self.spec.name));
+        }
+        let param = self.param(i)?;
+        self.sim.store(param, value)
+    }
+
+    pub fn output(&mut self, value: isize) -> Result<(), Error> {
+        self.sim.io.send_output(value)?;
+        if let Some(ref mut recording) = self.sim.recording {
+            recording.record_output(self.sim.steps, value);
+        }
+        self.sim.history.record_output(value);
+        Ok(())
+    }
+
+    // Continue at the given address instead of the next instruction.
+    pub fn jump(&mut self, address: usize) {
+        self.jump = Some(address);
+    }
+
+    pub fn halt(&mut self) {
+        self.sim.state = ProgramState::Halted;
+    }
+
+    pub fn pc(&self) -> usize {
+        self.sim.pc
+    }
+
+    pub fn relative_base(&self) -> usize {
+        self.sim.relative_base
+    }
+
+    pub fn peek(&self, address: usize) -> isize {
+        self.sim.peek(address)
+    }
+
+    pub fn poke(&mut self, address: usize, value: isize) {
+        self.sim.poke(address, value)
+    }
+
+    fn param(&self, i: usize) -> Result<Parameter, Error> {
+        self.params.get(i).cloned().ok_or_else(|| format_err!(
+            "{} has no parameter {}", self.spec.name, i))
+    }
+}
+
+impl Simulator {
+    // Run a custom op. Returns the jump target, if the op jumped.
+    pub(super) fn execute_custom(&mut self,
+                                 op: &Op) -> Result<Option<usize>, Error> {
+        let (opcode, params) = match op {
+            Op::Custom { opcode, params, .. } => (*opcode, params),
+            _ => unreachable!(),
+        };
+        let spec = self.instruction_set
+                       .get(opcode)
+                       .cloned()
+                       .ok_or_else(|| format_err!("No opcode {}", opcode))?;
+        let mut context = OpContext {
+            sim: self,
+            spec: &spec,
+            params: params,
+            jump: None,
+        };
+        (spec.callback)(&mut context)?;
+        Ok(context.jump)
+    }
+}
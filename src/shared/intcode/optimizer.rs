@@ -0,0 +1,540 @@
+// A peephole pass over a Program. Short runs of instructions are replaced with
+// fused instructions that the Simulator executes in one go:
+//
+//   - Add/Multiply with only immediate operands become a plain store of the
+//     folded constant.
+//   - Jumps to unconditional jumps (e.g. 1105,1,x) go straight to the final
+//     target.
+//   - A LessThan/Equal into a flag followed by a jump on that flag (the
+//     1008/1005 idiom) becomes a single compare-and-branch.
+//
+// Every address is considered as a possible instruction start, so nothing
+// needs to know where code ends and data begins: a fused instruction only
+// runs if the pc actually lands on it. Each one remembers the addresses it was
+// derived from and is dropped as soon as any of them is written, which keeps
+// self-modifying code correct. The step count still counts the original
+// instructions.
+
+use super::{Parameter, ParameterMode, Program, Simulator};
+use anyhow::Error;
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub(super) struct Operand {
+    mode: ParameterMode,
+    raw: isize,
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub(super) enum Fused {
+    // Folded Add/Multiply.
+    Store { dest: Operand, value: isize },
+    // Unconditional jump through a chain of `steps` jumps.
+    Jump { target: usize, steps: u64 },
+    // Conditional jump whose target is the start of a chain of unconditional
+    // jumps (target_steps of them).
+    Branch { cond: Operand, jump_if: bool, target: usize, target_steps: u64 },
+    // LessThan (or Equal) into flag, then a jump on the flag.
+    CompareBranch {
+        equal: bool,
+        x: Operand,
+        y: Operand,
+        flag: usize,
+        jump_if: bool,
+        target: usize,
+        target_steps: u64,
+    },
+}
+
+// A fused instruction, where it lives and the addresses it depends on.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Rewrite {
+    pub pc: usize,
+    fused: Fused,
+    covers: Vec<usize>,
+}
+
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct Optimization {
+    rewrites: Vec<Rewrite>,
+}
+
+// The fused instructions installed in a Simulator, indexed by pc, plus the
+// reverse mapping from each covered address to the fused instructions that
+// depend on it.
+#[derive(Default)]
+pub(super) struct FusedTable {
+    ops: Vec<Option<Fused>>,
+    dependents: Vec<Vec<usize>>,
+}
+
+// A decoded stock instruction, as far as the optimizer cares.
+struct Decoded {
+    opcode: isize,
+    operands: Vec<Operand>,
+}
+
+impl Program {
+    pub fn optimize(&self) -> Optimization {
+        let mem = &self.instructions;
+        let rewrites = (0..mem.len()).filter_map(|pc| rewrite_at(mem, pc))
+                                     .collect();
+        Optimization { rewrites: rewrites }
+    }
+}
+
+#[allow(dead_code)]
+impl Optimization {
+    pub fn rewrites(&self) -> &Vec<Rewrite> {
+        &self.rewrites
+    }
+
+    // Number of (folded constants, threaded jumps, fused compare-branches).
+    pub fn counts(&self) -> (usize, usize, usize) {
+        let mut counts = (0, 0, 0);
+        for rewrite in self.rewrites.iter() {
+            match rewrite.fused {
+                Fused::Store{..} => counts.0 += 1,
+                Fused::Jump{..} | Fused::Branch{..} => counts.1 += 1,
+                Fused::CompareBranch{..} => counts.2 += 1,
+            }
+        }
+        counts
+    }
+
+    pub(super) fn table(&self) -> FusedTable {
+        let mut table = FusedTable::default();
+        for rewrite in self.rewrites.iter() {
+            if rewrite.pc >= table.ops.len() {
+                table.ops.resize(rewrite.pc + 1, None);
+            }
+            table.ops[rewrite.pc] = Some(rewrite.fused);
+            for &addr in rewrite.covers.iter() {
+                if addr >= table.dependents.len() {
+                    table.dependents.resize_with(addr + 1, Vec::new);
+                }
+                table.dependents[addr].push(rewrite.pc);
+            }
+        }
+        table
+    }
+}
+
+impl FusedTable {
+    pub(super) fn is_active(&self) -> bool {
+        !self.ops.is_empty()
+    }
+
+    pub(super) fn get(&self, pc: usize) -> Option<Fused> {
+        self.ops.get(pc).cloned().flatten()
+    }
+
+    // Drop every fused instruction that depends on the given address.
+    pub(super) fn invalidate(&mut self, addr: usize) {
+        if let Some(dependents) = self.dependents.get_mut(addr) {
+            for pc in dependents.drain(..) {
+                self.ops[pc] = None;
+            }
+        }
+    }
+}
+
+impl Simulator {
+    pub(super) fn execute_fused(&mut self, fused: Fused) -> Result<(), Error> {
+        match fused {
+            Fused::Store{dest, value} => {
+                let dest = self.resolve(dest.mode, dest.raw)?;
+                self.store(dest, value)?;
+                self.pc += 4;
+                self.steps += 1;
+            },
+            Fused::Jump{target, steps} => {
+                self.track_threaded_jump(self.pc + 2, target);
+                self.pc = target;
+                self.steps += steps;
+            },
+            Fused::Branch{cond, jump_if, target, target_steps} => {
+                let cond = self.resolve(cond.mode, cond.raw)?;
+                if (self.load(cond) != 0) == jump_if {
+                    self.track_threaded_jump(self.pc + 2, target);
+                    self.pc = target;
+                    self.steps += 1 + target_steps;
+                } else {
+                    self.pc += 3;
+                    self.steps += 1;
+                }
+            },
+            Fused::CompareBranch{equal, x, y, flag, jump_if, target,
+                                 target_steps} => {
+                let x = self.load(self.resolve(x.mode, x.raw)?);
+                let y = self.load(self.resolve(y.mode, y.raw)?);
+                let result = if equal { x == y } else { x < y };
+                self.store(Parameter::Address(flag), result as isize)?;
+                if result == jump_if {
+                    self.track_threaded_jump(self.pc + 6, target);
+                    self.pc = target;
+                    self.steps += 2 + target_steps;
+                } else {
+                    self.pc += 7;
+                    self.steps += 2;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    // Track a jump threaded through to target (with its first target at
+    // the given operand address) the way taking the jumps one at a time
+    // would: the first one may be a call, and any of them may return. The
+    // jumps are still in memory, or the fused instruction would have been
+    // dropped.
+    fn track_threaded_jump(&mut self, operand: usize, target: usize) {
+        let mut hop = self.peek(operand) as usize;
+        self.track_jump(hop);
+        while hop != target {
+            hop = self.peek(hop + 2) as usize;
+            self.track_return(hop);
+        }
+    }
+}
+
+// Try to build a fused instruction starting at pc.
+fn rewrite_at(mem: &[isize], pc: usize) -> Option<Rewrite> {
+    let inst = decode(mem, pc)?;
+    let mut covers: Vec<usize> = (pc..pc+inst.size()).collect();
+    let fused = match inst.opcode {
+        1 | 2 => {
+            let (x, y, dest) = (inst.operands[0], inst.operands[1],
+                                inst.operands[2]);
+            if !x.is_immediate() || !y.is_immediate() || dest.is_immediate()
+               || (dest.mode == ParameterMode::Position && dest.raw < 0) {
+                return None;
+            }
+            let value = match inst.opcode {
+                1 => x.raw.checked_add(y.raw)?,
+                _ => x.raw.checked_mul(y.raw)?,
+            };
+            Fused::Store { dest: dest, value: value }
+        },
+        5 | 6 => {
+            let jump_if = inst.opcode == 5;
+            let (cond, dest) = (inst.operands[0], inst.operands[1]);
+            let target = immediate_target(dest)?;
+            let (target, target_steps) = thread(mem, target, &mut covers);
+            if target_steps == 0 {
+                return None;
+            }
+            if cond.is_immediate() {
+                if (cond.raw != 0) != jump_if {
+                    return None;
+                }
+                Fused::Jump { target: target, steps: 1 + target_steps }
+            } else {
+                Fused::Branch {
+                    cond: cond,
+                    jump_if: jump_if,
+                    target: target,
+                    target_steps: target_steps,
+                }
+            }
+        },
+        7 | 8 => {
+            let (x, y, flag) = (inst.operands[0], inst.operands[1],
+                                inst.operands[2]);
+            let branch = decode(mem, pc + 4)?;
+            if flag.mode != ParameterMode::Position || flag.raw < 0
+               || (branch.opcode != 5 && branch.opcode != 6)
+               || branch.operands[0] != flag {
+                return None;
+            }
+            // The flag can't be written into the fused instructions
+            let flag = flag.raw as usize;
+            if flag >= pc && flag < pc + 7 {
+                return None;
+            }
+            covers.extend(pc+4..pc+7);
+            let target = immediate_target(branch.operands[1])?;
+            let (target, target_steps) = thread(mem, target, &mut covers);
+            // or into a jump threaded through
+            if covers.contains(&flag) {
+                return None;
+            }
+            Fused::CompareBranch {
+                equal: inst.opcode == 8,
+                x: x,
+                y: y,
+                flag: flag,
+                jump_if: branch.opcode == 5,
+                target: target,
+                target_steps: target_steps,
+            }
+        },
+        _ => return None,
+    };
+    Some(Rewrite { pc: pc, fused: fused, covers: covers })
+}
+
+// Decode the stock instruction at pc, if it is one the optimizer handles.
+fn decode(mem: &[isize], pc: usize) -> Option<Decoded> {
+    let word = *mem.get(pc)?;
+    if word < 0 {
+        return None;
+    }
+    let opcode = word % 100;
+    let arity = match opcode {
+        1 | 2 | 7 | 8 => 3,
+        5 | 6 => 2,
+        _ => return None,
+    };
+    let mut operands = Vec::new();
+    for i in 0..arity {
+        operands.push(Operand {
+            mode: Simulator::parameter_mode(word, i).ok()?,
+            raw: *mem.get(pc + i + 1)?,
+        });
+    }
+    Some(Decoded { opcode: opcode, operands: operands })
+}
+
+// Follow a chain of unconditional jumps starting at target. Returns the final
+// target and the number of jumps taken, and adds the jumps to covers.
+fn thread(mem: &[isize], mut target: usize,
+          covers: &mut Vec<usize>) -> (usize, u64) {
+    let mut seen = HashSet::new();
+    let mut steps = 0;
+    while seen.insert(target) {
+        let next = match unconditional_jump(mem, target) {
+            Some(next) => next,
+            None => break,
+        };
+        covers.extend(target..target+3);
+        steps += 1;
+        target = next;
+    }
+    (target, steps)
+}
+
+// The target of the instruction at pc if it always jumps to a fixed address.
+fn unconditional_jump(mem: &[isize], pc: usize) -> Option<usize> {
+    let inst = decode(mem, pc)?;
+    let (cond, dest) = match inst.opcode {
+        5 | 6 => (inst.operands[0], inst.operands[1]),
+        _ => return None,
+    };
+    if !cond.is_immediate() || (cond.raw != 0) != (inst.opcode == 5) {
+        return None;
+    }
+    immediate_target(dest)
+}
+
+fn immediate_target(dest: Operand) -> Option<usize> {
+    if dest.is_immediate() && dest.raw >= 0 {
+        Some(dest.raw as usize)
+    } else {
+        None
+    }
+}
+
+impl Operand {
+    fn is_immediate(&self) -> bool {
+        self.mode == ParameterMode::Immediate
+    }
+}
+
+impl Decoded {
+    fn size(&self) -> usize {
+        self.operands.len() + 1
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mode {
+            ParameterMode::Position => write!(f, "[{}]", self.raw),
+            ParameterMode::Immediate => write!(f, "{}", self.raw),
+            ParameterMode::Relative => write!(f, "[rb{:+}]", self.raw),
+        }
+    }
+}
+
+impl fmt::Display for Fused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let jump = |jump_if: bool| if jump_if { "jt" } else { "jf" };
+        match self {
+            Fused::Store{dest, value} => write!(f, "{} = {}", dest, value),
+            Fused::Jump{target, steps} => {
+                write!(f, "jmp {} ({} jumps)", target, steps)
+            },
+            Fused::Branch{cond, jump_if, target, ..} => {
+                write!(f, "{} {}, {}", jump(*jump_if), cond, target)
+            },
+            Fused::CompareBranch{equal, x, y, flag, jump_if, target, ..} => {
+                write!(f, "[{}] = {} {} {}; {} [{}], {}",
+                       flag, x, if *equal { "==" } else { "<" }, y,
+                       jump(*jump_if), flag, target)
+            },
+        }
+    }
+}
+
+impl fmt::Display for Optimization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for rewrite in self.rewrites.iter() {
+            writeln!(f, "{:5}: {}", rewrite.pc, rewrite.fused)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::fuzz;
+
+    fn run(sim: &mut Simulator, inputs: &[isize]) -> Vec<isize> {
+        let input = sim.create_input_channel();
+        let output = sim.create_output_channel();
+        for &value in inputs {
+            input.send(value).unwrap();
+        }
+        sim.run().unwrap();
+        output.try_iter().collect()
+    }
+
+    // Run a day's program with and without optimization in lockstep.
+    fn check_day(day: &str, pokes: &[(usize, isize)], inputs: &[isize]) {
+        let program = Program::from_path(
+            &format!("input/day{}.txt", day)).unwrap();
+        let mut plain = Simulator::with_program(&program);
+        let mut optimized = Simulator::with_optimized_program(&program);
+        for &(addr, value) in pokes {
+            plain.poke(addr, value);
+            optimized.poke(addr, value);
+        }
+        if let Some(divergence) = fuzz::compare_engines(plain, optimized,
+                                                        inputs, 2_000_000) {
+            panic!("day {}: {}", day, divergence);
+        }
+    }
+
+    // Inputs for programs that take whatever they are given.
+    fn cycle(values: &[isize], n: usize) -> Vec<isize> {
+        values.iter().cloned().cycle().take(n).collect()
+    }
+
+    #[test]
+    fn test_rewrites() {
+        let text = "1008,20,5,21,1005,21,11,104,0,99,0,\
+                    1105,1,14,104,1,99,0,0,0,5,0";
+        let program = Program::from_string(text).unwrap();
+        let optimization = program.optimize();
+        let pcs: Vec<usize> = optimization.rewrites()
+                                          .iter()
+                                          .map(|r| r.pc)
+                                          .collect();
+        assert_eq!(vec![0, 4], pcs);
+        assert_eq!((0, 1, 1), optimization.counts());
+        assert_eq!("    0: [21] = [20] == 5; jt [21], 14\n    4: jt [21], 14\n",
+                   optimization.to_string());
+
+        let mut plain = Simulator::with_program(&program);
+        let mut optimized = Simulator::with_optimized_program(&program);
+        assert_eq!(vec![1], run(&mut plain, &[]));
+        assert_eq!(vec![1], run(&mut optimized, &[]));
+        assert_eq!(5, plain.steps());
+        assert_eq!(5, optimized.steps());
+    }
+
+    #[test]
+    fn test_self_modifying_code() {
+        // The first instruction overwrites an operand of the second, which
+        // must not be folded using the original value.
+        let program = Program::from_string("1101,0,7,6,1101,1,0,20,4,20,99")
+                              .unwrap();
+        assert_eq!((2, 0, 0), program.optimize().counts());
+        let mut sim = Simulator::with_optimized_program(&program);
+        assert_eq!(vec![8], run(&mut sim, &[]));
+    }
+
+    #[test]
+    fn test_flag_in_threaded_jump() {
+        // The compare's flag is the condition of the jump its branch goes
+        // to, so the jump can't be threaded through.
+        let program = Program::from_string("1008,20,5,11,1006,11,10,104,7,99,\
+                                            1105,1,16,104,1,99,104,2,99,0,0")
+                              .unwrap();
+        let mut plain = Simulator::with_program(&program);
+        let mut optimized = Simulator::with_optimized_program(&program);
+        assert_eq!(vec![1], run(&mut plain, &[]));
+        assert_eq!(vec![1], run(&mut optimized, &[]));
+        assert_eq!(plain.steps(), optimized.steps());
+    }
+
+    #[test]
+    fn test_return_from_fused_branch() {
+        // The function at 20 returns by branching straight to its return
+        // address, which has to pop its frame just like a return does.
+        let mut text = String::from("109,100,21101,9,0,0,1105,1,20,104,5,99,\
+                                     0,0,0,0,0,0,0,0,1008,50,0,51,1005,51,9");
+        text.push_str(&",0".repeat(25));
+        let program = Program::from_string(&text).unwrap();
+        assert_eq!((1, 0, 1), program.optimize().counts());
+        let plain = Simulator::with_program(&program);
+        let optimized = Simulator::with_optimized_program(&program);
+        if let Some(divergence) = fuzz::compare_engines(plain, optimized,
+                                                        &[], 1000) {
+            panic!("{}", divergence);
+        }
+    }
+
+    #[test]
+    fn test_call_through_trampoline() {
+        // The call at 6 goes to 12, which only jumps on to the function at
+        // 15. The frame is for 12 whether or not the jumps are threaded.
+        let program = Program::from_string("109,100,21101,9,0,0,1105,1,12,\
+                                            104,7,99,1105,1,15,104,5,99")
+                              .unwrap();
+        assert_eq!((1, 1, 0), program.optimize().counts());
+        let mut plain = Simulator::with_program(&program);
+        let mut optimized = Simulator::with_optimized_program(&program);
+        assert_eq!(vec![5], run(&mut plain, &[]));
+        assert_eq!(vec![5], run(&mut optimized, &[]));
+        assert_eq!(plain.call_stack(), optimized.call_stack());
+        let entries: Vec<usize> = plain.call_stack().iter()
+                                       .map(|f| f.entry)
+                                       .collect();
+        assert_eq!(vec![12], entries);
+    }
+
+    #[test]
+    fn test_random_programs() {
+        let make_a = |p: &Program| Simulator::with_program(p);
+        let make_b = |p: &Program| Simulator::with_optimized_program(p);
+        let failure = fuzz::fuzz(7, 500, &make_a, &make_b, 1000);
+        assert!(failure.is_none(), "{}", failure.unwrap());
+    }
+
+    #[test]
+    fn test_days() {
+        check_day("02", &[(1, 12), (2, 2)], &[]);
+        check_day("02", &[(1, 69), (2, 79)], &[]);
+        check_day("05", &[], &[1]);
+        check_day("05", &[], &[5]);
+        check_day("07", &[], &[3, 17]);
+        check_day("07", &[], &[7, 0, 12, 40]);
+        check_day("09", &[], &[1]);
+        check_day("09", &[], &[2]);
+        check_day("11", &[], &cycle(&[0, 1, 1, 0, 1], 500));
+        check_day("13", &[(0, 2)], &cycle(&[0, -1, 1, 1, 0], 2000));
+        check_day("15", &[], &cycle(&[1, 4, 2, 3, 3, 1, 4], 1000));
+
+        // Days 2 and 7 happen not to use any of the idioms.
+        for day in ["05", "09", "11", "13", "15"].iter() {
+            let program = Program::from_path(
+                &format!("input/day{}.txt", day)).unwrap();
+            let (folded, threaded, fused) = program.optimize().counts();
+            assert!(folded + threaded + fused > 0, "day {}", day);
+        }
+    }
+}
@@ -0,0 +1,250 @@
+// Program loading. Text programs are integers separated by commas and/or
+// whitespace, with '#' starting a comment that runs to the end of the line. A
+// line may start with "addr:" to say where its values go, which makes
+// annotated listings (like disassembler output) loadable as-is; any gap is
+// filled with zeros.
+//
+// There is also a compact binary format: the magic bytes followed by each
+// value as a zigzag-encoded LEB128 varint.
+
+use super::Program;
+use std::error;
+use std::fmt;
+
+pub const MAGIC: &[u8] = b"\0ICB1";
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Location {
+    // 1-based line and column.
+    Text { line: usize, column: usize },
+    // Offset of the byte where decoding failed.
+    Binary { offset: usize },
+}
+
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub struct ParseError {
+    pub location: Location,
+    // Address of the value that failed to parse.
+    pub index: usize,
+    pub message: String,
+}
+
+pub(super) fn parse_text(text: &str) -> Result<Program, ParseError> {
+    let mut values: Vec<isize> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let error = |column: usize, index: usize, message: String| {
+            ParseError {
+                location: Location::Text { line: i + 1, column: column + 1 },
+                index: index,
+                message: message,
+            }
+        };
+        let line = match line.find('#') {
+            Some(pos) => &line[..pos],
+            None => line,
+        };
+        let mut start = 0;
+        if let Some(pos) = line.find(':') {
+            let label = line[..pos].trim();
+            let column = line.len() - line.trim_start().len();
+            let addr = label.parse::<usize>().map_err(|_| error(
+                column, values.len(),
+                format!("Invalid address '{}'", label)))?;
+            if addr < values.len() {
+                return Err(error(column, values.len(), format!(
+                    "Address {} overlaps earlier values (next is {})",
+                    addr, values.len())));
+            }
+            values.resize(addr, 0);
+            start = pos + 1;
+        }
+        for (column, token) in tokens(line, start) {
+            let value = token.parse::<isize>().map_err(|_| error(
+                column, values.len(),
+                format!("Invalid value '{}'", token)))?;
+            values.push(value);
+        }
+    }
+    Ok(Program { instructions: values })
+}
+
+pub(super) fn parse_binary(bytes: &[u8]) -> Result<Program, ParseError> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(ParseError {
+            location: Location::Binary { offset: 0 },
+            index: 0,
+            message: "Missing binary program header".to_string(),
+        });
+    }
+    let mut values = Vec::new();
+    let mut offset = MAGIC.len();
+    while offset < bytes.len() {
+        let start = offset;
+        let mut encoded: u64 = 0;
+        let mut shift = 0;
+        loop {
+            let error = |offset: usize, message: &str| ParseError {
+                location: Location::Binary { offset: offset },
+                index: values.len(),
+                message: message.to_string(),
+            };
+            let byte = *bytes.get(offset).ok_or_else(|| error(
+                start, "Truncated value"))?;
+            if shift > 63 || (shift == 63 && byte > 1) {
+                return Err(error(offset, "Value too large"));
+            }
+            encoded |= ((byte & 0x7f) as u64) << shift;
+            offset += 1;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        let value = (encoded >> 1) as i64 ^ -((encoded & 1) as i64);
+        values.push(value as isize);
+    }
+    Ok(Program { instructions: values })
+}
+
+pub(super) fn to_binary(program: &Program) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    for &value in program.instructions.iter() {
+        let value = value as i64;
+        let mut encoded = ((value << 1) ^ (value >> 63)) as u64;
+        loop {
+            let byte = (encoded & 0x7f) as u8;
+            encoded >>= 7;
+            if encoded == 0 {
+                bytes.push(byte);
+                break;
+            }
+            bytes.push(byte | 0x80);
+        }
+    }
+    bytes
+}
+
+// The values in line[start..], with their (0-based) columns.
+fn tokens(line: &str, start: usize) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut token_start = None;
+    for (i, c) in line[start..].char_indices() {
+        let i = i + start;
+        if c == ',' || c.is_whitespace() {
+            if let Some(s) = token_start.take() {
+                tokens.push((s, &line[s..i]));
+            }
+        } else if token_start.is_none() {
+            token_start = Some(i);
+        }
+    }
+    if let Some(s) = token_start {
+        tokens.push((s, &line[s..]));
+    }
+    tokens
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Text { line, column } => {
+                write!(f, "line {}, column {}", line, column)
+            },
+            Location::Binary { offset } => write!(f, "byte {}", offset),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (address {}): {}", self.location, self.index,
+               self.message)
+    }
+}
+
+impl error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(text: &str) -> ParseError {
+        Program::from_string(text).err()
+                                  .expect("should not parse")
+                                  .downcast::<ParseError>()
+                                  .unwrap()
+    }
+
+    #[test]
+    fn test_separators_and_comments() {
+        let text = "# Adds two numbers\n\
+                    1,5,6,7,   # add\n\
+                    99\n\
+                    \n\
+                    3 4\t0,\n";
+        let program = Program::from_string(text).unwrap();
+        assert_eq!(vec![1, 5, 6, 7, 99, 3, 4, 0], program.instructions);
+    }
+
+    #[test]
+    fn test_annotated() {
+        let text = "0: 1101, 2, 3, 8\n\
+                    4: 4, 8  # out\n\
+                    6: 99\n\
+                    9: -1\n";
+        let program = Program::from_string(text).unwrap();
+        assert_eq!(vec![1101, 2, 3, 8, 4, 8, 99, 0, 0, -1],
+                   program.instructions);
+    }
+
+    #[test]
+    fn test_error_locations() {
+        assert_eq!(ParseError {
+            location: Location::Text { line: 2, column: 4 },
+            index: 4,
+            message: "Invalid value '7x'".to_string(),
+        }, parse_error("1,2,3\n4, 7x, 5"));
+        assert_eq!(ParseError {
+            location: Location::Text { line: 2, column: 1 },
+            index: 4,
+            message: "Address 2 overlaps earlier values (next is 4)"
+                         .to_string(),
+        }, parse_error("0: 1,2,3,4\n2: 5"));
+        assert_eq!("line 1, column 3 (address 0): Invalid address 'x'",
+                   parse_error("  x: 1").to_string());
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let program = Program::from_path("input/day09.txt").unwrap();
+        let bytes = program.to_bytes();
+        assert!(bytes.len() < program.instructions.len() * 3);
+        assert_eq!(program.instructions,
+                   Program::from_bytes(&bytes).unwrap().instructions);
+
+        let extremes = Program {
+            instructions: vec![0, -1, 1, isize::MIN, isize::MAX],
+        };
+        assert_eq!(extremes.instructions,
+                   Program::from_bytes(&extremes.to_bytes()).unwrap()
+                                                            .instructions);
+
+        let path = std::env::temp_dir()
+            .join(format!("advent2019_program_{}.icb", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let loaded = Program::from_path(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(program.instructions, loaded.unwrap().instructions);
+    }
+
+    #[test]
+    fn test_binary_errors() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(&[2, 0x80]);
+        let error = Program::from_bytes(&bytes).err().unwrap()
+                                               .downcast::<ParseError>()
+                                               .unwrap();
+        assert_eq!("byte 6 (address 1): Truncated value", error.to_string());
+        assert!(Program::from_bytes(b"1,2,3").is_err());
+    }
+}
@@ -0,0 +1,132 @@
+// Recording of the values a Simulator consumes and emits, so that a session
+// (e.g. an arcade game or a droid exploration) can be saved to disk and
+// replayed deterministically later.
+
+use anyhow::{Error, format_err};
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum IoEvent {
+    Input { step: u64, value: isize },
+    Output { step: u64, value: isize },
+}
+
+#[derive(Clone,Debug,Default,Eq,PartialEq)]
+pub struct Recording {
+    events: Vec<IoEvent>,
+}
+
+#[allow(dead_code)]
+impl Recording {
+    pub fn new() -> Self {
+        Self {
+            events: Vec::new(),
+        }
+    }
+
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        Self::from_string(&text)
+    }
+
+    // One event per line, "in <step> <value>" or "out <step> <value>". Blank
+    // lines and lines starting with '#' are ignored.
+    pub fn from_string(text: &str) -> Result<Self, Error> {
+        let mut recording = Self::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 3 {
+                return Err(format_err!("Bad event on line {}: {}", i+1, line));
+            }
+            let step = fields[1].parse::<u64>()?;
+            let value = fields[2].parse::<isize>()?;
+            recording.events.push(match fields[0] {
+                "in" => IoEvent::Input { step, value },
+                "out" => IoEvent::Output { step, value },
+                other => return Err(format_err!(
+                    "Bad event type on line {}: {}", i+1, other)),
+            });
+        }
+        Ok(recording)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        File::create(path)?.write_all(self.to_string().as_bytes())?;
+        Ok(())
+    }
+
+    pub fn events(&self) -> &Vec<IoEvent> {
+        &self.events
+    }
+
+    // The input values in the order they were consumed.
+    pub fn inputs<'a>(&'a self) -> impl Iterator<Item=isize> + 'a {
+        self.events.iter().filter_map(|e| match e {
+            &IoEvent::Input { value, .. } => Some(value),
+            _ => None,
+        })
+    }
+
+    // Compare against another recording (treating self as the expected one)
+    // and report the first event that differs.
+    pub fn verify(&self, actual: &Recording) -> Result<(), Error> {
+        for (i, expected) in self.events.iter().enumerate() {
+            match actual.events.get(i) {
+                Some(event) if event == expected => {},
+                Some(event) => {
+                    return Err(format_err!(
+                        "Replay diverged at event {}: expected {}, got {}",
+                        i, expected, event));
+                },
+                None => {
+                    return Err(format_err!(
+                        "Replay ended early at event {}: expected {}",
+                        i, expected));
+                },
+            }
+        }
+        if actual.events.len() > self.events.len() {
+            return Err(format_err!(
+                "Replay produced extra event {}: {}",
+                self.events.len(), actual.events[self.events.len()]));
+        }
+        Ok(())
+    }
+
+    pub(super) fn record_input(&mut self, step: u64, value: isize) {
+        self.events.push(IoEvent::Input { step, value });
+    }
+
+    pub(super) fn record_output(&mut self, step: u64, value: isize) {
+        self.events.push(IoEvent::Output { step, value });
+    }
+}
+
+impl fmt::Display for IoEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IoEvent::Input { step, value } => {
+                write!(f, "in {} {}", step, value)
+            },
+            IoEvent::Output { step, value } => {
+                write!(f, "out {} {}", step, value)
+            },
+        }
+    }
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for event in self.events.iter() {
+            writeln!(f, "{}", event)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+// Snapshots of a Simulator's machine state: memory, registers, step count and
+// any input it has been sent but not consumed yet. A snapshot can be restored
+// into any Simulator, which makes it possible to resume a session later or to
+// branch several runs off the same point. I/O channels are not part of it.
+//
+// The text form has one "key value" line per field, values of lists separated
+// by commas:
+//
+//   pc 29
+//   rb 1024
+//   steps 1532
+//   state wait
+//   input 1,-1
+//   mem 1,380,379,385,...
+
+use super::{call_stack, inspector, optimizer, ProgramState, Simulator};
+use anyhow::{Error, format_err};
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct Snapshot {
+    mem: Vec<isize>,
+    pc: usize,
+    relative_base: usize,
+    steps: u64,
+    state: ProgramState,
+    pending_input: Vec<isize>,
+}
+
+#[allow(dead_code)]
+impl Simulator {
+    pub fn snapshot(&mut self) -> Snapshot {
+        self.io.buffer_input();
+        Snapshot {
+            mem: self.mem.clone(),
+            pc: self.pc,
+            relative_base: self.relative_base,
+            steps: self.steps,
+            state: self.state.clone(),
+            pending_input: self.io.pending_input.iter().cloned().collect(),
+        }
+    }
+
+    // Replace the machine state with the snapshot's. Input still waiting on
+    // the channel is discarded. Optimizations, history and the call stack are
+    // reset as when loading a program.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.mem = snapshot.mem.clone();
+        self.pc = snapshot.pc;
+        self.relative_base = snapshot.relative_base;
+        self.steps = snapshot.steps;
+        self.state = snapshot.state.clone();
+        if let Some(ref receiver) = self.io.input_reader {
+            while receiver.try_recv().is_ok() {}
+        }
+        self.io.pending_input = snapshot.pending_input.iter()
+                                                      .cloned()
+                                                      .collect();
+        self.strict.reset(snapshot.mem.len());
+        self.fused = optimizer::FusedTable::default();
+        self.history = inspector::History::default();
+        self.calls = call_stack::CallStack::default();
+    }
+
+    // Construct a Simulator in the snapshot's state, for convenience.
+    pub fn with_snapshot(snapshot: &Snapshot) -> Self {
+        let mut sim = Self::new();
+        sim.restore(snapshot);
+        sim
+    }
+}
+
+#[allow(dead_code)]
+impl Snapshot {
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        Self::from_string(&text)
+    }
+
+    // Blank lines and lines starting with '#' are ignored. Every field but
+    // the input is required.
+    pub fn from_string(text: &str) -> Result<Self, Error> {
+        let (mut mem, mut pc, mut rb, mut steps, mut state) =
+            (None, None, None, None, None);
+        let mut pending_input = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |e: Error| format_err!("Line {}: {}", i+1, e);
+            let number = |value: &str| {
+                value.parse::<u64>().map_err(|e| error(e.into()))
+            };
+            let (key, value) = match line.find(' ') {
+                Some(pos) => (&line[..pos], line[pos+1..].trim()),
+                None => (line, ""),
+            };
+            match key {
+                "pc" => pc = Some(number(value)? as usize),
+                "rb" => rb = Some(number(value)? as usize),
+                "steps" => steps = Some(number(value)?),
+                "state" => state = Some(match value {
+                    "running" => ProgramState::Running,
+                    "wait" => ProgramState::Wait,
+                    "halted" => ProgramState::Halted,
+                    _ => return Err(error(format_err!(
+                        "Bad state: {}", value))),
+                }),
+                "input" => pending_input = parse_list(value).map_err(error)?,
+                "mem" => mem = Some(parse_list(value).map_err(error)?),
+                _ => return Err(error(format_err!("Unknown key: {}", key))),
+            }
+        }
+        let missing = |key: &str| format_err!("Snapshot has no {}", key);
+        Ok(Self {
+            mem: mem.ok_or_else(|| missing("mem"))?,
+            pc: pc.ok_or_else(|| missing("pc"))?,
+            relative_base: rb.ok_or_else(|| missing("rb"))?,
+            steps: steps.ok_or_else(|| missing("steps"))?,
+            state: state.ok_or_else(|| missing("state"))?,
+            pending_input: pending_input,
+        })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        File::create(path)?.write_all(self.to_string().as_bytes())?;
+        Ok(())
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+}
+
+fn parse_list(text: &str) -> Result<Vec<isize>, Error> {
+    text.split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<isize>().map_err(|e| format_err!("{}: {}", s, e)))
+        .collect()
+}
+
+fn join(values: &[isize]) -> String {
+    values.iter()
+          .map(|v| v.to_string())
+          .collect::<Vec<String>>()
+          .join(",")
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state {
+            ProgramState::Running => "running",
+            ProgramState::Wait => "wait",
+            ProgramState::Halted => "halted",
+        };
+        write!(f, "pc {}\nrb {}\nsteps {}\nstate {}\n",
+               self.pc, self.relative_base, self.steps, state)?;
+        if !self.pending_input.is_empty() {
+            writeln!(f, "input {}", join(&self.pending_input))?;
+        }
+        writeln!(f, "mem {}", join(&self.mem))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Program;
+
+    // Adds pairs of inputs until it reads a zero.
+    const ADDER: &str = "3,100,1006,100,16,3,101,1,100,101,102,4,102,\
+                         1105,1,0,99";
+
+    #[test]
+    fn test_restore() {
+        let program = Program::from_string(ADDER).unwrap();
+        let mut sim = Simulator::with_program(&program);
+        let input = sim.create_input_channel();
+        let output = sim.create_output_channel();
+        input.send(2).unwrap();
+        input.send(3).unwrap();
+        sim.run().unwrap();
+        assert_eq!(5, output.recv().unwrap());
+        input.send(10).unwrap();
+        let snapshot = sim.snapshot();
+        assert_eq!(vec![10], snapshot.pending_input);
+
+        // Branch off twice from the same point
+        for &(second, sum) in [(20, 30), (-4, 6)].iter() {
+            sim.restore(&snapshot);
+            input.send(second).unwrap();
+            sim.run().unwrap();
+            assert_eq!(sum, output.recv().unwrap());
+        }
+
+        let mut copy = Simulator::with_snapshot(&snapshot);
+        let copy_input = copy.create_input_channel();
+        let copy_output = copy.create_output_channel();
+        copy_input.send(1).unwrap();
+        copy_input.send(0).unwrap();
+        copy.run().unwrap();
+        assert_eq!(11, copy_output.recv().unwrap());
+        assert!(!copy.is_running());
+        assert_eq!(snapshot.steps() + 9, copy.steps());
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        let program = Program::from_string(ADDER).unwrap();
+        let mut sim = Simulator::with_program(&program);
+        let input = sim.create_input_channel();
+        let _output = sim.create_output_channel();
+        input.send(-7).unwrap();
+        sim.run().unwrap();
+        let snapshot = sim.snapshot();
+        let text = snapshot.to_string();
+        assert!(text.starts_with("pc 5\nrb 0\nsteps 2\nstate wait\nmem 3,"),
+                "{}", text);
+        assert_eq!(snapshot, Snapshot::from_string(&text).unwrap());
+
+        let path = std::env::temp_dir()
+            .join(format!("advent2019_snapshot_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        snapshot.save(path).unwrap();
+        let loaded = Snapshot::from_path(path);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(snapshot, loaded.unwrap());
+
+        assert_eq!("Snapshot has no pc",
+                   Snapshot::from_string("rb 0\nsteps 0\nstate wait\nmem 99")
+                            .unwrap_err().to_string());
+        assert_eq!("Line 2: Bad state: paused",
+                   Snapshot::from_string("pc 0\nstate paused")
+                            .unwrap_err().to_string());
+    }
+}
@@ -0,0 +1,148 @@
+// Optional checks for suspicious memory accesses: reading addresses beyond the
+// program image that were never written (the Simulator happily returns 0 for
+// those) and writing to addresses that have been executed as code.
+
+use super::{Op, Parameter, Simulator};
+use anyhow::{Error, format_err};
+use std::collections::HashSet;
+use std::fmt;
+
+#[allow(dead_code)]
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Strictness {
+    Off,
+    Warn,
+    Error,
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub struct StrictMode {
+    pub uninitialized_reads: Strictness,
+    pub code_writes: Strictness,
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum ViolationKind {
+    UninitializedRead,
+    CodeWrite,
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub address: usize,
+    pub pc: usize,
+}
+
+// Bookkeeping kept by the Simulator while strict mode is enabled.
+#[derive(Default)]
+pub(super) struct StrictState {
+    pub(super) mode: StrictMode,
+    pub(super) image_size: usize,
+    pub(super) written: HashSet<usize>,
+    pub(super) executed: HashSet<usize>,
+    pub(super) warnings: Vec<Violation>,
+}
+
+impl Default for StrictMode {
+    fn default() -> Self {
+        StrictMode::all(Strictness::Off)
+    }
+}
+
+#[allow(dead_code)]
+impl StrictMode {
+    // Same strictness for every check.
+    pub fn all(strictness: Strictness) -> Self {
+        StrictMode {
+            uninitialized_reads: strictness,
+            code_writes: strictness,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.uninitialized_reads != Strictness::Off ||
+        self.code_writes != Strictness::Off
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ViolationKind::UninitializedRead => {
+                write!(f, "Read of uninitialized address {} (pc: {})",
+                       self.address, self.pc)
+            },
+            ViolationKind::CodeWrite => {
+                write!(f, "Write to code at address {} (pc: {})",
+                       self.address, self.pc)
+            },
+        }
+    }
+}
+
+impl StrictState {
+    pub(super) fn reset(&mut self, image_size: usize) {
+        self.image_size = image_size;
+        self.written.clear();
+        self.executed.clear();
+        self.warnings.clear();
+    }
+
+    fn report(&mut self, strictness: Strictness,
+              violation: Violation) -> Result<(), Error> {
+        match strictness {
+            Strictness::Off => Ok(()),
+            Strictness::Warn => {
+                self.warnings.push(violation);
+                Ok(())
+            },
+            Strictness::Error => Err(format_err!("{}", violation)),
+        }
+    }
+}
+
+impl Simulator {
+    // Check the instruction about to be executed: its own words and the
+    // addresses it reads must have been initialized. Also remembers the
+    // instruction's words as code.
+    pub(super) fn check_strict_reads(&mut self, op: &Op) -> Result<(), Error> {
+        let pc = self.pc;
+        let mut reads: Vec<usize> = (pc..pc+op.size()).collect();
+        reads.extend(op.reads().iter().filter_map(|p| match *p {
+            Parameter::Address(addr) => Some(addr),
+            Parameter::Value(_) => None,
+        }));
+        for addr in reads {
+            if addr >= self.strict.image_size &&
+               !self.strict.written.contains(&addr) {
+                let violation = Violation {
+                    kind: ViolationKind::UninitializedRead,
+                    address: addr,
+                    pc: pc,
+                };
+                let strictness = self.strict.mode.uninitialized_reads;
+                self.strict.report(strictness, violation)?;
+            }
+        }
+        for addr in pc..pc+op.size() {
+            self.strict.executed.insert(addr);
+        }
+        Ok(())
+    }
+
+    // Check a write the running program is about to make.
+    pub(super) fn check_strict_write(&mut self,
+                                     addr: usize) -> Result<(), Error> {
+        if self.strict.executed.contains(&addr) {
+            let violation = Violation {
+                kind: ViolationKind::CodeWrite,
+                address: addr,
+                pc: self.pc,
+            };
+            let strictness = self.strict.mode.code_writes;
+            self.strict.report(strictness, violation)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,463 @@
+// Symbolic execution of Intcode. Selected memory cells and input values are
+// treated as symbols, and arithmetic builds expression trees instead of
+// numbers. This works as long as control flow doesn't depend on a symbol;
+// when it does, execution stops and the caller has to fall back to concrete
+// search.
+
+use super::Program;
+use anyhow::{Error, format_err};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::ops::RangeInclusive;
+
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub enum Expr {
+    Const(isize),
+    Symbol(String),
+    Add(Box<Expr>, Box<Expr>),
+    Multiply(Box<Expr>, Box<Expr>),
+    LessThan(Box<Expr>, Box<Expr>),
+    Equal(Box<Expr>, Box<Expr>),
+    // Whatever is in memory at a symbolic address.
+    Load(Box<Expr>),
+}
+
+// sum(coefficient * symbol) + constant
+#[derive(Clone,Debug,Default,Eq,PartialEq)]
+pub struct Linear {
+    pub terms: BTreeMap<String, isize>,
+    pub constant: isize,
+}
+
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub enum Outcome {
+    Halted,
+    // Execution can't continue without knowing a symbol's value.
+    NeedsConcrete { pc: usize, reason: String },
+}
+
+pub struct Executor {
+    mem: Vec<Expr>,
+    pc: usize,
+    relative_base: isize,
+    inputs: VecDeque<Expr>,
+    outputs: Vec<Expr>,
+}
+
+impl Expr {
+    pub fn symbol(name: &str) -> Expr {
+        Expr::Symbol(name.to_string())
+    }
+
+    pub fn add(x: Expr, y: Expr) -> Result<Expr, Error> {
+        Ok(match (x, y) {
+            (Expr::Const(a), Expr::Const(b)) => {
+                Expr::Const(a.checked_add(b).ok_or_else(|| format_err!(
+                    "Overflow in opcode 1: {} + {}", a, b))?)
+            },
+            (Expr::Const(0), e) | (e, Expr::Const(0)) => e,
+            (x, y) => Expr::Add(Box::new(x), Box::new(y)),
+        })
+    }
+
+    pub fn multiply(x: Expr, y: Expr) -> Result<Expr, Error> {
+        Ok(match (x, y) {
+            (Expr::Const(a), Expr::Const(b)) => {
+                Expr::Const(a.checked_mul(b).ok_or_else(|| format_err!(
+                    "Overflow in opcode 2: {} * {}", a, b))?)
+            },
+            (Expr::Const(0), _) | (_, Expr::Const(0)) => Expr::Const(0),
+            (Expr::Const(1), e) | (e, Expr::Const(1)) => e,
+            (x, y) => Expr::Multiply(Box::new(x), Box::new(y)),
+        })
+    }
+
+    pub fn less_than(x: Expr, y: Expr) -> Expr {
+        match (x, y) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const((a < b) as isize),
+            (x, y) => Expr::LessThan(Box::new(x), Box::new(y)),
+        }
+    }
+
+    pub fn equal(x: Expr, y: Expr) -> Expr {
+        match (x, y) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const((a == b) as isize),
+            (x, y) if x == y => Expr::Const(1),
+            (x, y) => Expr::Equal(Box::new(x), Box::new(y)),
+        }
+    }
+
+    pub fn as_const(&self) -> Option<isize> {
+        match self {
+            &Expr::Const(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    // The expression as a linear combination of symbols, if it is one (and
+    // its coefficients fit in an isize).
+    pub fn to_linear(&self) -> Option<Linear> {
+        match self {
+            &Expr::Const(v) => {
+                Some(Linear { constant: v, ..Linear::default() })
+            },
+            Expr::Symbol(name) => {
+                let mut linear = Linear::default();
+                linear.terms.insert(name.clone(), 1);
+                Some(linear)
+            },
+            Expr::Add(x, y) => {
+                let (mut x, y) = (x.to_linear()?, y.to_linear()?);
+                for (name, coeff) in y.terms {
+                    let sum = x.terms.entry(name).or_insert(0);
+                    *sum = sum.checked_add(coeff)?;
+                }
+                x.constant = x.constant.checked_add(y.constant)?;
+                x.terms.retain(|_, &mut c| c != 0);
+                Some(x)
+            },
+            Expr::Multiply(x, y) => {
+                let (x, y) = (x.to_linear()?, y.to_linear()?);
+                let (scale, mut linear) = if x.terms.is_empty() {
+                    (x.constant, y)
+                } else if y.terms.is_empty() {
+                    (y.constant, x)
+                } else {
+                    return None;
+                };
+                for coeff in linear.terms.values_mut() {
+                    *coeff = coeff.checked_mul(scale)?;
+                }
+                linear.constant = linear.constant.checked_mul(scale)?;
+                linear.terms.retain(|_, &mut c| c != 0);
+                Some(linear)
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Const(v) => write!(f, "{}", v),
+            Expr::Symbol(name) => write!(f, "{}", name),
+            Expr::Add(x, y) => write!(f, "({} + {})", x, y),
+            Expr::Multiply(x, y) => write!(f, "({} * {})", x, y),
+            Expr::LessThan(x, y) => write!(f, "({} < {})", x, y),
+            Expr::Equal(x, y) => write!(f, "({} == {})", x, y),
+            Expr::Load(addr) => write!(f, "mem[{}]", addr),
+        }
+    }
+}
+
+impl Linear {
+    // Find values for the symbols, each within its domain, making the
+    // expression equal to target. Symbols that don't appear in the
+    // expression get the lowest value of their domain.
+    pub fn solve(&self, target: isize,
+                 domains: &[(&str, RangeInclusive<isize>)])
+        -> Option<HashMap<String, isize>>
+    {
+        if self.terms.keys().any(|t| !domains.iter().any(|(d, _)| d == t)) {
+            return None;
+        }
+        let mut assignment: HashMap<String, isize> =
+            domains.iter()
+                   .map(|(name, range)| (name.to_string(), *range.start()))
+                   .collect();
+        let vars: Vec<(&String, isize)> = self.terms.iter()
+                                              .map(|(n, &c)| (n, c))
+                                              .collect();
+        let domain = |name: &str| {
+            domains.iter().find(|(d, _)| *d == name).unwrap().1.clone()
+        };
+        let remaining = target.checked_sub(self.constant)?;
+        if self.solve_from(&vars, remaining, &domain, &mut assignment) {
+            Some(assignment)
+        } else {
+            None
+        }
+    }
+
+    // Enumerate all but the last variable, then solve for the last one.
+    // Values that would overflow an isize along the way are skipped.
+    fn solve_from<F>(&self, vars: &[(&String, isize)], remaining: isize,
+                     domain: &F, assignment: &mut HashMap<String, isize>)
+        -> bool
+        where F: Fn(&str) -> RangeInclusive<isize>
+    {
+        match vars.split_first() {
+            None => remaining == 0,
+            Some((&(name, coeff), [])) => {
+                match remaining.checked_div(coeff) {
+                    Some(value) if remaining % coeff == 0
+                                   && domain(name).contains(&value) => {
+                        assignment.insert(name.clone(), value);
+                        true
+                    },
+                    _ => false,
+                }
+            },
+            Some((&(name, coeff), rest)) => {
+                for value in domain(name) {
+                    let product = coeff.checked_mul(value);
+                    let left = match product.and_then(|p| {
+                        remaining.checked_sub(p)
+                    }) {
+                        Some(left) => left,
+                        None => continue,
+                    };
+                    assignment.insert(name.clone(), value);
+                    if self.solve_from(rest, left, domain, assignment) {
+                        return true;
+                    }
+                }
+                false
+            },
+        }
+    }
+}
+
+impl Executor {
+    pub fn new(program: &Program) -> Self {
+        Executor {
+            mem: program.instructions.iter()
+                                     .map(|&v| Expr::Const(v))
+                                     .collect(),
+            pc: 0,
+            relative_base: 0,
+            inputs: VecDeque::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    // Replace the contents of a memory cell with a symbol.
+    pub fn symbolize_cell(&mut self, address: usize, name: &str) {
+        self.write(address, Expr::symbol(name));
+    }
+
+    // Queue a value (symbolic or not) for the program's next input.
+    #[allow(dead_code)]
+    pub fn add_input(&mut self, value: Expr) {
+        self.inputs.push_back(value);
+    }
+
+    pub fn peek(&self, address: usize) -> Expr {
+        self.mem.get(address).cloned().unwrap_or(Expr::Const(0))
+    }
+
+    #[allow(dead_code)]
+    pub fn outputs(&self) -> &Vec<Expr> {
+        &self.outputs
+    }
+
+    pub fn run(&mut self) -> Result<Outcome, Error> {
+        loop {
+            let pc = self.pc;
+            let needs_concrete = |reason: &str| Ok(Outcome::NeedsConcrete {
+                pc: pc,
+                reason: reason.to_string(),
+            });
+            let inst = match self.peek(pc).as_const() {
+                Some(inst) => inst,
+                None => return needs_concrete("symbolic opcode"),
+            };
+            match inst % 100 {
+                opcode @ 1 | opcode @ 2 | opcode @ 7 | opcode @ 8 => {
+                    let x = self.param(inst, 0)?;
+                    let y = self.param(inst, 1)?;
+                    let dest = match self.address_param(inst, 2)? {
+                        Some(dest) => dest,
+                        None => return needs_concrete("symbolic address"),
+                    };
+                    self.write(dest, match opcode {
+                        1 => Expr::add(x, y)?,
+                        2 => Expr::multiply(x, y)?,
+                        7 => Expr::less_than(x, y),
+                        _ => Expr::equal(x, y),
+                    });
+                    self.pc += 4;
+                },
+                3 => {
+                    let dest = match self.address_param(inst, 0)? {
+                        Some(dest) => dest,
+                        None => return needs_concrete("symbolic address"),
+                    };
+                    match self.inputs.pop_front() {
+                        Some(value) => self.write(dest, value),
+                        None => return needs_concrete("no input available"),
+                    }
+                    self.pc += 2;
+                },
+                4 => {
+                    let value = self.param(inst, 0)?;
+                    self.outputs.push(value);
+                    self.pc += 2;
+                },
+                opcode @ 5 | opcode @ 6 => {
+                    let cond = match self.param(inst, 0)?.as_const() {
+                        Some(cond) => cond,
+                        None => return needs_concrete("branch on symbol"),
+                    };
+                    if (cond != 0) == (opcode == 5) {
+                        match self.param(inst, 1)?.as_const() {
+                            Some(dest) if dest >= 0 => self.pc = dest as usize,
+                            Some(dest) => return Err(format_err!(
+                                "Invalid jump target: {} (pc: {})", dest, pc)),
+                            None => return needs_concrete("symbolic jump"),
+                        }
+                    } else {
+                        self.pc += 3;
+                    }
+                },
+                9 => {
+                    match self.param(inst, 0)?.as_const() {
+                        Some(offset) => self.relative_base += offset,
+                        None => return needs_concrete("symbolic base"),
+                    }
+                    self.pc += 2;
+                },
+                99 => return Ok(Outcome::Halted),
+                _ => return Err(format_err!("Invalid opcode: {} (pc: {})",
+                                            inst, pc)),
+            }
+        }
+    }
+
+    // Value of the ith parameter of the current instruction.
+    fn param(&self, inst: isize, i: usize) -> Result<Expr, Error> {
+        let raw = self.peek(self.pc + i + 1);
+        match Self::mode(inst, i)? {
+            1 => Ok(raw),
+            mode => match self.param_address(mode, raw)? {
+                Ok(addr) => Ok(self.peek(addr)),
+                Err(addr) => Ok(Expr::Load(Box::new(addr))),
+            },
+        }
+    }
+
+    // Address of the ith parameter of the current instruction, if it isn't
+    // symbolic.
+    fn address_param(&self, inst: isize,
+                     i: usize) -> Result<Option<usize>, Error> {
+        let raw = self.peek(self.pc + i + 1);
+        match Self::mode(inst, i)? {
+            1 => Err(format_err!("Cannot store using immediate parameter")),
+            mode => Ok(self.param_address(mode, raw)?.ok()),
+        }
+    }
+
+    // The concrete address for a position/relative parameter, or the symbolic
+    // expression for it.
+    fn param_address(&self, mode: isize,
+                     raw: Expr) -> Result<Result<usize, Expr>, Error> {
+        let addr = if mode == 2 {
+            Expr::add(Expr::Const(self.relative_base), raw)?
+        } else {
+            raw
+        };
+        match addr.as_const() {
+            Some(a) if a < 0 => Err(format_err!("Invalid address: {}", a)),
+            Some(a) => Ok(Ok(a as usize)),
+            None => Ok(Err(addr)),
+        }
+    }
+
+    fn mode(inst: isize, i: usize) -> Result<isize, Error> {
+        match (inst / (100 * 10_isize.pow(i as u32))) % 10 {
+            mode @ 0..=2 => Ok(mode),
+            mode => Err(format_err!("Invalid parameter mode: {}", mode)),
+        }
+    }
+
+    fn write(&mut self, address: usize, value: Expr) {
+        if address >= self.mem.len() {
+            self.mem.resize(address+1, Expr::Const(0));
+        }
+        self.mem[address] = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_expression() {
+        // mem[0] = (a + 2) * 3 + b
+        let program = Program::from_string(
+            "1001,13,2,13,1002,13,3,13,1,13,14,0,99,0,0").unwrap();
+        let mut exec = Executor::new(&program);
+        exec.symbolize_cell(13, "a");
+        exec.symbolize_cell(14, "b");
+        assert_eq!(Outcome::Halted, exec.run().unwrap());
+        let linear = exec.peek(0).to_linear().unwrap();
+        assert_eq!(6, linear.constant);
+        assert_eq!(Some(&3), linear.terms.get("a"));
+        assert_eq!(Some(&1), linear.terms.get("b"));
+
+        let solution = linear.solve(100, &[("a", 0..=99), ("b", 0..=2)])
+                             .unwrap();
+        assert_eq!(3 * solution["a"] + solution["b"] + 6, 100);
+        assert!(linear.solve(100, &[("a", 0..=9), ("b", 0..=2)]).is_none());
+    }
+
+    #[test]
+    fn test_linear_overflow() {
+        let big = || Box::new(Expr::Const(isize::MAX));
+        let a = || Box::new(Expr::Symbol("a".to_string()));
+        assert!(Expr::Multiply(big(), Box::new(Expr::Add(a(), a())))
+                    .to_linear().is_none());
+        assert!(Expr::Add(big(), Box::new(Expr::Const(1)))
+                    .to_linear().is_none());
+
+        // Values that overflow are passed over
+        let linear = Expr::Add(Box::new(Expr::Multiply(big(), a())),
+                               Box::new(Expr::Symbol("b".to_string())))
+                         .to_linear().unwrap();
+        let domains = [("a", -2..=2), ("b", -2..=2)];
+        assert_eq!(Some(-1), linear.solve(isize::MIN, &domains)
+                                   .map(|s| s["b"]));
+        assert_eq!(Some(1), linear.solve(isize::MAX, &domains)
+                                  .map(|s| s["a"]));
+        assert!(Linear { constant: -1, ..linear }.solve(isize::MAX, &domains)
+                                                 .is_none());
+    }
+
+    #[test]
+    fn test_symbolic_address_is_opaque() {
+        // mem[9] = mem[a] + mem[b]; mem[0] = a * b is not linear.
+        let program = Program::from_string(
+            "1,1,1,9,2,1,2,0,99,0").unwrap();
+        let mut exec = Executor::new(&program);
+        exec.symbolize_cell(1, "a");
+        exec.symbolize_cell(2, "b");
+        assert_eq!(Outcome::Halted, exec.run().unwrap());
+        assert_eq!("(mem[a] + mem[b])", exec.peek(9).to_string());
+        assert_eq!("(a * b)", exec.peek(0).to_string());
+        assert!(exec.peek(0).to_linear().is_none());
+    }
+
+    #[test]
+    fn test_symbolic_input_and_branch() {
+        // Outputs input + 1, then branches on it.
+        let program = Program::from_string(
+            "3,13,1001,13,1,13,4,13,1005,13,12,99,99,0").unwrap();
+        let mut exec = Executor::new(&program);
+        exec.add_input(Expr::symbol("in"));
+        assert_eq!(Outcome::NeedsConcrete {
+                       pc: 8,
+                       reason: "branch on symbol".to_string(),
+                   },
+                   exec.run().unwrap());
+        assert_eq!(vec![Expr::add(Expr::symbol("in"), Expr::Const(1))
+                             .unwrap()],
+                   *exec.outputs());
+
+        let mut exec = Executor::new(&program);
+        exec.add_input(Expr::Const(-1));
+        assert_eq!(Outcome::Halted, exec.run().unwrap());
+        assert_eq!(vec![Expr::Const(0)], *exec.outputs());
+    }
+}
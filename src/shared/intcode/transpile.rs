@@ -0,0 +1,600 @@
+// Translation of an Intcode program into standalone Rust source. The code
+// reachable from address 0 is split into basic blocks, each of which becomes
+// an arm of a `match pc` dispatcher; memory is a Vec that grows on demand.
+// The generated program reads its inputs from stdin (separated by whitespace
+// or commas) and prints each output on its own line.
+//
+// Jumps to computed addresses (e.g. returning from a function) may land
+// anywhere, so the generated program also carries a small interpreter that
+// runs instructions one at a time until it reaches the start of a block. Once
+// the program writes to its translated code, everything is interpreted from
+// then on.
+//
+// Overflow, invalid addresses and invalid opcodes make the generated program
+// panic with the message the Simulator fails with.
+
+use super::{ParameterMode, Program, Simulator};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+#[derive(Clone,Copy)]
+struct Operand {
+    mode: ParameterMode,
+    raw: isize,
+}
+
+#[derive(Clone)]
+enum Inst {
+    Add(Operand, Operand, Operand),
+    Multiply(Operand, Operand, Operand),
+    Input(Operand),
+    Output(Operand),
+    JumpIfTrue(Operand, Operand),
+    JumpIfFalse(Operand, Operand),
+    LessThan(Operand, Operand, Operand),
+    Equal(Operand, Operand, Operand),
+    AdjustRelativeBase(Operand),
+    Halt,
+    Invalid(isize),
+}
+
+const PRELUDE: &str = r#"use std::io::Read;
+
+fn add(x: i64, y: i64) -> i64 {
+    x.checked_add(y).unwrap_or_else(|| {
+        panic!("Overflow in opcode 1: {} + {}", x, y)
+    })
+}
+
+fn mul(x: i64, y: i64) -> i64 {
+    x.checked_mul(y).unwrap_or_else(|| {
+        panic!("Overflow in opcode 2: {} * {}", x, y)
+    })
+}
+
+struct Machine {
+    mem: Vec<i64>,
+    pc: usize,
+    rb: i64,
+    input: std::vec::IntoIter<i64>,
+    modified: bool,
+}
+
+impl Machine {
+    fn rd(&self, addr: i64) -> i64 {
+        if addr < 0 {
+            panic!("Invalid address: {}", addr);
+        }
+        self.mem.get(addr as usize).cloned().unwrap_or(0)
+    }
+
+    // Returns true if the write changed translated code.
+    fn wr(&mut self, addr: i64, value: i64) -> bool {
+        if addr < 0 {
+            panic!("Invalid address: {}", addr);
+        }
+        let addr = addr as usize;
+        if addr >= self.mem.len() {
+            self.mem.resize(addr + 1, 0);
+        }
+        if is_code(addr) && self.mem[addr] != value {
+            self.modified = true;
+        }
+        self.mem[addr] = value;
+        self.modified
+    }
+
+    fn input(&mut self) -> i64 {
+        self.input.next().expect("Ran out of input")
+    }
+
+    fn output(&mut self, value: i64) {
+        println!("{}", value);
+    }
+
+    // Execute a single instruction the slow way. Returns false on halt.
+    fn step(&mut self) -> bool {
+        let op = self.rd(self.pc as i64);
+        let mode = |i: u32| op / 10_i64.pow(i + 2) % 10;
+        let addr = |m: &Machine, i: u32| {
+            let raw = m.rd(m.pc as i64 + 1 + i as i64);
+            match mode(i) {
+                0 => raw,
+                2 => m.rb + raw,
+                _ => panic!("Invalid parameter mode (pc: {})", m.pc),
+            }
+        };
+        let arg = |m: &Machine, i: u32| match mode(i) {
+            1 => m.rd(m.pc as i64 + 1 + i as i64),
+            _ => m.rd(addr(m, i)),
+        };
+        match op % 100 {
+            1 | 2 | 7 | 8 => {
+                let (x, y) = (arg(self, 0), arg(self, 1));
+                let value = match op % 100 {
+                    1 => add(x, y),
+                    2 => mul(x, y),
+                    7 => (x < y) as i64,
+                    _ => (x == y) as i64,
+                };
+                let dest = addr(self, 2);
+                self.wr(dest, value);
+                self.pc += 4;
+            },
+            3 => {
+                let value = self.input();
+                let dest = addr(self, 0);
+                self.wr(dest, value);
+                self.pc += 2;
+            },
+            4 => {
+                let value = arg(self, 0);
+                self.output(value);
+                self.pc += 2;
+            },
+            5 | 6 => {
+                if (arg(self, 0) != 0) == (op % 100 == 5) {
+                    self.pc = arg(self, 1) as usize;
+                } else {
+                    self.pc += 3;
+                }
+            },
+            9 => {
+                self.rb += arg(self, 0);
+                self.pc += 2;
+            },
+            99 => return false,
+            _ => panic!("Invalid opcode: {} (pc: {})", op, self.pc),
+        }
+        true
+    }
+"#;
+
+const MAIN: &str = r#"
+fn main() {
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text).unwrap();
+    let input: Vec<i64> = text.split(|c: char| c == ',' || c.is_whitespace())
+                              .filter(|s| !s.is_empty())
+                              .map(|s| s.parse().expect("Bad input value"))
+                              .collect();
+    let mut machine = Machine {
+        mem: IMAGE.to_vec(),
+        pc: 0,
+        rb: 0,
+        input: input.into_iter(),
+        modified: false,
+    };
+    machine.run();
+}
+"#;
+
+impl Program {
+    pub fn to_rust(&self) -> String {
+        let mem = &self.instructions;
+        let (insts, leaders) = explore(mem);
+        let mut out = String::new();
+        out.push_str("// Generated from an Intcode program by advent2019 \
+                      --transpile.\n");
+        out.push_str(PRELUDE);
+        out.push_str("\n    fn run(&mut self) {\n");
+        out.push_str("        loop {\n");
+        out.push_str("            if self.modified {\n");
+        out.push_str("                if !self.step() {\n");
+        out.push_str("                    return;\n");
+        out.push_str("                }\n");
+        out.push_str("                continue;\n");
+        out.push_str("            }\n");
+        out.push_str("            match self.pc {\n");
+        let code = code_ranges(&insts);
+        for &leader in leaders.iter() {
+            write_block(&mut out, &insts, &leaders, &code, leader);
+        }
+        out.push_str("                _ => {\n");
+        out.push_str("                    if !self.step() {\n");
+        out.push_str("                        return;\n");
+        out.push_str("                    }\n");
+        out.push_str("                },\n");
+        out.push_str("            }\n");
+        out.push_str("        }\n");
+        out.push_str("    }\n");
+        out.push_str("}\n\n");
+        write_is_code(&mut out, &code);
+        write_image(&mut out, mem);
+        out.push_str(MAIN);
+        out
+    }
+}
+
+// Find the instructions statically reachable from address 0 and the addresses
+// where basic blocks start.
+fn explore(mem: &[isize]) -> (BTreeMap<usize, Inst>, BTreeSet<usize>) {
+    let mut insts = BTreeMap::new();
+    let mut leaders = BTreeSet::new();
+    let mut pending = vec![0];
+    leaders.insert(0);
+    while let Some(mut addr) = pending.pop() {
+        while !insts.contains_key(&addr) {
+            let inst = decode(mem, addr);
+            let next = addr + inst.size();
+            let mut falls_through = true;
+            match &inst {
+                Inst::JumpIfTrue(cond, dest)
+                | Inst::JumpIfFalse(cond, dest) => {
+                    if dest.is_immediate() && dest.raw >= 0 {
+                        leaders.insert(dest.raw as usize);
+                        pending.push(dest.raw as usize);
+                    }
+                    let always = match inst {
+                        Inst::JumpIfTrue(..) => cond.raw != 0,
+                        _ => cond.raw == 0,
+                    };
+                    if cond.is_immediate() && always {
+                        falls_through = false;
+                    } else {
+                        leaders.insert(next);
+                    }
+                },
+                Inst::Halt | Inst::Invalid(_) => falls_through = false,
+                _ => {},
+            }
+            insts.insert(addr, inst);
+            if !falls_through {
+                break;
+            }
+            addr = next;
+        }
+    }
+    (insts, leaders)
+}
+
+fn write_block(out: &mut String, insts: &BTreeMap<usize, Inst>,
+               leaders: &BTreeSet<usize>, code: &[(usize, usize)],
+               leader: usize) {
+    writeln!(out, "                {} => {{", leader).unwrap();
+    let mut addr = leader;
+    loop {
+        let inst = &insts[&addr];
+        let next = addr + inst.size();
+        writeln!(out, "                    // {}: {}", addr, inst).unwrap();
+        for line in inst.to_rust(addr, next, code) {
+            writeln!(out, "                    {}", line).unwrap();
+        }
+        if inst.ends_block() {
+            break;
+        }
+        if leaders.contains(&next) || !insts.contains_key(&next) {
+            writeln!(out, "                    self.pc = {};", next).unwrap();
+            break;
+        }
+        addr = next;
+    }
+    out.push_str("                },\n");
+}
+
+// Address ranges (inclusive) covered by the translated instructions.
+fn code_ranges(insts: &BTreeMap<usize, Inst>) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (&addr, inst) in insts.iter() {
+        let end = addr + inst.size() - 1;
+        match ranges.last_mut() {
+            Some(last) if addr <= last.1 + 1 => last.1 = last.1.max(end),
+            _ => ranges.push((addr, end)),
+        }
+    }
+    ranges
+}
+
+fn write_is_code(out: &mut String, code: &[(usize, usize)]) {
+    let ranges: Vec<String> = code.iter()
+                                  .map(|(a, b)| format!("{}..={}", a, b))
+                                  .collect();
+    out.push_str("fn is_code(addr: usize) -> bool {\n");
+    out.push_str("    match addr {\n");
+    for chunk in ranges.chunks(6) {
+        writeln!(out, "        {} => true,", chunk.join(" | ")).unwrap();
+    }
+    out.push_str("        _ => false,\n");
+    out.push_str("    }\n");
+    out.push_str("}\n\n");
+}
+
+fn write_image(out: &mut String, mem: &[isize]) {
+    out.push_str("const IMAGE: &[i64] = &[\n");
+    for chunk in mem.chunks(8) {
+        let values: Vec<String> = chunk.iter().map(|v| v.to_string()).collect();
+        writeln!(out, "    {},", values.join(", ")).unwrap();
+    }
+    out.push_str("];\n");
+}
+
+fn decode(mem: &[isize], addr: usize) -> Inst {
+    let word = mem.get(addr).cloned().unwrap_or(0);
+    let operand = |i: usize| {
+        Simulator::parameter_mode(word, i).ok().map(|mode| Operand {
+            mode: mode,
+            raw: mem.get(addr + i + 1).cloned().unwrap_or(0),
+        })
+    };
+    let operands = |n: usize| (0..n).map(operand)
+                                   .collect::<Option<Vec<Operand>>>();
+    if word < 0 {
+        return Inst::Invalid(word);
+    }
+    let inst = match word % 100 {
+        1 => operands(3).map(|o| Inst::Add(o[0], o[1], o[2])),
+        2 => operands(3).map(|o| Inst::Multiply(o[0], o[1], o[2])),
+        3 => operands(1).map(|o| Inst::Input(o[0])),
+        4 => operands(1).map(|o| Inst::Output(o[0])),
+        5 => operands(2).map(|o| Inst::JumpIfTrue(o[0], o[1])),
+        6 => operands(2).map(|o| Inst::JumpIfFalse(o[0], o[1])),
+        7 => operands(3).map(|o| Inst::LessThan(o[0], o[1], o[2])),
+        8 => operands(3).map(|o| Inst::Equal(o[0], o[1], o[2])),
+        9 => operands(1).map(|o| Inst::AdjustRelativeBase(o[0])),
+        99 => Some(Inst::Halt),
+        _ => None,
+    };
+    match inst {
+        Some(Inst::Input(dest)) if dest.is_immediate() => Inst::Invalid(word),
+        Some(Inst::Add(_, _, dest)) | Some(Inst::Multiply(_, _, dest)) |
+        Some(Inst::LessThan(_, _, dest)) | Some(Inst::Equal(_, _, dest))
+            if dest.is_immediate() => Inst::Invalid(word),
+        Some(inst) => inst,
+        None => Inst::Invalid(word),
+    }
+}
+
+impl Operand {
+    fn is_immediate(&self) -> bool {
+        self.mode == ParameterMode::Immediate
+    }
+
+    // Rust expression for the operand's value.
+    fn value(&self) -> String {
+        match self.mode {
+            ParameterMode::Immediate => self.raw.to_string(),
+            _ => format!("self.rd({})", self.address()),
+        }
+    }
+
+    // Rust expression for the address the operand refers to.
+    fn address(&self) -> String {
+        match self.mode {
+            ParameterMode::Relative if self.raw < 0 => {
+                format!("self.rb - {}", -self.raw)
+            },
+            ParameterMode::Relative if self.raw > 0 => {
+                format!("self.rb + {}", self.raw)
+            },
+            ParameterMode::Relative => "self.rb".to_string(),
+            _ => self.raw.to_string(),
+        }
+    }
+
+    // True unless the operand is a fixed address outside the code.
+    fn may_hit(&self, code: &[(usize, usize)]) -> bool {
+        match self.mode {
+            ParameterMode::Position => code.iter().any(|&(a, b)| {
+                self.raw >= a as isize && self.raw <= b as isize
+            }),
+            _ => true,
+        }
+    }
+
+    fn jump_target(&self) -> String {
+        match self.mode {
+            ParameterMode::Immediate if self.raw >= 0 => self.raw.to_string(),
+            _ => format!("{} as usize", self.value()),
+        }
+    }
+}
+
+impl Inst {
+    fn size(&self) -> usize {
+        match self {
+            Inst::Add(..) | Inst::Multiply(..) |
+            Inst::LessThan(..) | Inst::Equal(..) => 4,
+            Inst::JumpIfTrue(..) | Inst::JumpIfFalse(..) => 3,
+            Inst::Input(..) | Inst::Output(..) |
+            Inst::AdjustRelativeBase(..) => 2,
+            Inst::Halt | Inst::Invalid(_) => 1,
+        }
+    }
+
+    fn ends_block(&self) -> bool {
+        match self {
+            Inst::JumpIfTrue(cond, _) if cond.is_immediate() => cond.raw != 0,
+            Inst::JumpIfFalse(cond, _) if cond.is_immediate() => cond.raw == 0,
+            Inst::Halt | Inst::Invalid(_) => true,
+            _ => false,
+        }
+    }
+
+    // Rust statements for the instruction at addr. A store that could change
+    // translated code leaves the block, so the rest runs interpreted.
+    fn to_rust(&self, addr: usize, next: usize,
+               code: &[(usize, usize)]) -> Vec<String> {
+        let store = |dest: &Operand, value: String| {
+            let write = format!("self.wr({}, {})", dest.address(), value);
+            if dest.may_hit(code) {
+                vec![format!("if {} {{", write),
+                     format!("    self.pc = {};", next),
+                     "    continue;".to_string(),
+                     "}".to_string()]
+            } else {
+                vec![format!("{};", write)]
+            }
+        };
+        let jump = |test: String, dest: &Operand| {
+            vec![format!("if {} {{", test),
+                 format!("    self.pc = {};", dest.jump_target()),
+                 "    continue;".to_string(),
+                 "}".to_string()]
+        };
+        match self {
+            Inst::Add(x, y, dest) => {
+                store(dest, format!("add({}, {})", x.value(), y.value()))
+            },
+            Inst::Multiply(x, y, dest) => {
+                store(dest, format!("mul({}, {})", x.value(), y.value()))
+            },
+            Inst::Input(dest) => {
+                let mut lines = vec!["let value = self.input();".to_string()];
+                lines.extend(store(dest, "value".to_string()));
+                lines
+            },
+            Inst::Output(value) => vec![
+                format!("self.output({});", value.value())],
+            Inst::JumpIfTrue(_, dest) | Inst::JumpIfFalse(_, dest)
+                if self.ends_block() => {
+                vec![format!("self.pc = {};", dest.jump_target())]
+            },
+            Inst::JumpIfTrue(cond, dest) => {
+                jump(format!("{} != 0", cond.value()), dest)
+            },
+            Inst::JumpIfFalse(cond, dest) => {
+                jump(format!("{} == 0", cond.value()), dest)
+            },
+            Inst::LessThan(x, y, dest) => store(dest, format!(
+                "({} < {}) as i64", x.value(), y.value())),
+            Inst::Equal(x, y, dest) => store(dest, format!(
+                "({} == {}) as i64", x.value(), y.value())),
+            Inst::AdjustRelativeBase(offset) => vec![
+                format!("self.rb += {};", offset.value())],
+            Inst::Halt => vec!["return;".to_string()],
+            Inst::Invalid(word) => vec![format!(
+                "panic!(\"Invalid opcode: {} (pc: {})\");", word, addr)],
+        }
+    }
+}
+
+impl std::fmt::Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.mode {
+            ParameterMode::Position => write!(f, "[{}]", self.raw),
+            ParameterMode::Immediate => write!(f, "{}", self.raw),
+            ParameterMode::Relative => write!(f, "[rb{:+}]", self.raw),
+        }
+    }
+}
+
+impl std::fmt::Display for Inst {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Inst::Add(x, y, d) => write!(f, "add {}, {} -> {}", x, y, d),
+            Inst::Multiply(x, y, d) => write!(f, "mul {}, {} -> {}", x, y, d),
+            Inst::Input(d) => write!(f, "in -> {}", d),
+            Inst::Output(v) => write!(f, "out {}", v),
+            Inst::JumpIfTrue(c, d) => write!(f, "jt {}, {}", c, d),
+            Inst::JumpIfFalse(c, d) => write!(f, "jf {}, {}", c, d),
+            Inst::LessThan(x, y, d) => write!(f, "lt {}, {} -> {}", x, y, d),
+            Inst::Equal(x, y, d) => write!(f, "eq {}, {} -> {}", x, y, d),
+            Inst::AdjustRelativeBase(o) => write!(f, "arb {}", o),
+            Inst::Halt => write!(f, "halt"),
+            Inst::Invalid(word) => write!(f, "invalid {}", word),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    // Compile the program's Rust translation and run it with the given inputs.
+    fn execute_transpiled(name: &str, program: &Program,
+                          inputs: &[isize]) -> std::process::Output {
+        let dir = std::env::temp_dir();
+        let name = format!("advent2019_{}_{}", name, std::process::id());
+        let source = dir.join(format!("{}.rs", name));
+        let binary = dir.join(name);
+        File::create(&source).unwrap()
+            .write_all(program.to_rust().as_bytes()).unwrap();
+        let status = Command::new("rustc")
+                             .args(["--edition", "2018", "-O", "-o"])
+                             .arg(&binary)
+                             .arg(&source)
+                             .status()
+                             .unwrap();
+        assert!(status.success(), "failed to compile {:?}", source);
+        let mut child = Command::new(&binary)
+                                .stdin(Stdio::piped())
+                                .stdout(Stdio::piped())
+                                .stderr(Stdio::piped())
+                                .spawn()
+                                .unwrap();
+        let text: Vec<String> = inputs.iter().map(|v| v.to_string()).collect();
+        child.stdin.take().unwrap()
+             .write_all(text.join("\n").as_bytes()).unwrap();
+        let output = child.wait_with_output().unwrap();
+        std::fs::remove_file(&source).unwrap();
+        std::fs::remove_file(&binary).unwrap();
+        output
+    }
+
+    fn run_transpiled(name: &str, program: &Program,
+                      inputs: &[isize]) -> Vec<isize> {
+        let output = execute_transpiled(name, program, inputs);
+        assert!(output.status.success(), "{}",
+                String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect()
+    }
+
+    fn run_simulated(program: &Program, inputs: &[isize]) -> Vec<isize> {
+        let mut sim = Simulator::with_program(program);
+        let input = sim.create_input_channel();
+        let output = sim.create_output_channel();
+        for &value in inputs {
+            input.send(value).unwrap();
+        }
+        sim.run().unwrap();
+        output.try_iter().collect()
+    }
+
+    #[test]
+    fn test_quine() {
+        let program = Program::from_string(
+            "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99")
+            .unwrap();
+        let expected = run_simulated(&program, &[]);
+        assert_eq!(program.instructions, expected);
+        assert_eq!(expected, run_transpiled("quine", &program, &[]));
+    }
+
+    #[test]
+    fn test_self_modifying_code() {
+        // Day 5 starts by patching one of its own instructions.
+        let program = Program::from_path("input/day05.txt").unwrap();
+        assert_eq!(run_simulated(&program, &[5]),
+                   run_transpiled("day05", &program, &[5]));
+    }
+
+    #[test]
+    fn test_overflow() {
+        // Once in a block and once interpreted, after the first instruction
+        // modifies the code.
+        for text in ["1101,9223372036854775807,1,5,99,0",
+                     "1101,1,1101,1,1102,4611686018427387904,2,3,99"].iter() {
+            let program = Program::from_string(text).unwrap();
+            let error = Simulator::with_program(&program).run().unwrap_err()
+                                                         .to_string();
+            assert!(error.starts_with("Overflow in opcode"), "{}", error);
+            let output = execute_transpiled("overflow", &program, &[]);
+            let stderr = String::from_utf8(output.stderr).unwrap();
+            assert!(!output.status.success());
+            assert!(stderr.contains(&error), "{}", stderr);
+        }
+    }
+
+    #[test]
+    fn test_day09() {
+        let program = Program::from_path("input/day09.txt").unwrap();
+        assert_eq!(run_simulated(&program, &[1]),
+                   run_transpiled("day09", &program, &[1]));
+    }
+}
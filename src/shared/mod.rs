@@ -1,4 +1,12 @@
+pub mod asciicast;
+pub mod bitmap;
+pub mod explorer;
 pub mod grid;
 pub mod intcode;
+pub mod ocr;
+pub mod options;
+pub mod painter;
+pub mod stopwatch;
+pub mod terminal;
 
 mod infinite_grid;
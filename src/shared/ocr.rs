@@ -0,0 +1,97 @@
+use anyhow::{Error, format_err};
+
+/* Reads the capital letters some puzzles draw, in the usual Advent of Code
+ * font: 4 pixels wide and 6 tall, with a blank column between letters. The
+ * picture is given as rows of pixels, true where lit. Blank columns on either
+ * side are ignored. */
+
+const WIDTH: usize = 4;
+const HEIGHT: usize = 6;
+
+// The letters known to appear, as far as anyone has collected them.
+const FONT: &[(char, [&str; HEIGHT])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', ["###.", ".#..", ".#..", ".#..", ".#..", "###."]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+pub fn read_letters(pixels: &[Vec<bool>]) -> Result<String, Error> {
+    if pixels.len() != HEIGHT {
+        return Err(format_err!("Letters are {} pixels tall, not {}",
+                               HEIGHT, pixels.len()));
+    }
+    let width = pixels.iter().map(|row| row.len()).max().unwrap_or(0);
+    let lit = |row: usize, col: usize| {
+        pixels[row].get(col).cloned().unwrap_or(false)
+    };
+    let blank = |col: usize| (0..HEIGHT).all(|row| !lit(row, col));
+
+    let mut text = String::new();
+    let mut col = 0;
+    loop {
+        while col < width && blank(col) {
+            col += 1;
+        }
+        if col >= width {
+            break;
+        }
+        let glyph: Vec<String> = (0..HEIGHT).map(|row| {
+            (col..col+WIDTH).map(|c| if lit(row, c) { '#' } else { '.' })
+                            .collect()
+        }).collect();
+        let letter = FONT.iter()
+                         .find(|(_, art)| art.iter().eq(glyph.iter()))
+                         .map(|&(letter, _)| letter)
+                         .ok_or_else(|| {
+                             format_err!("Unknown letter at column {}:\n{}",
+                                         col, glyph.join("\n"))
+                         })?;
+        text.push(letter);
+        col += WIDTH + 1;
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixels(art: &[&str]) -> Vec<Vec<bool>> {
+        art.iter().map(|row| row.chars().map(|c| c == '#').collect())
+                  .collect()
+    }
+
+    #[test]
+    fn read_font() {
+        // Every letter in the font, in a row, with a margin
+        let art: Vec<String> = (0..HEIGHT).map(|row| {
+            let glyphs: Vec<&str> = FONT.iter().map(|(_, a)| a[row])
+                                        .collect();
+            format!("..{}..", glyphs.join("."))
+        }).collect();
+        let art: Vec<&str> = art.iter().map(|s| s.as_str()).collect();
+        let letters: String = FONT.iter().map(|&(c, _)| c).collect();
+        assert_eq!(letters, read_letters(&pixels(&art)).unwrap());
+
+        assert_eq!("", read_letters(&vec![Vec::new(); HEIGHT]).unwrap());
+        assert!(read_letters(&pixels(&["#"])).is_err());
+        let err = read_letters(&pixels(&["#", "", "", "", "", "#"]))
+                      .unwrap_err();
+        assert_eq!("Unknown letter at column 0:\n#...\n....\n....\n\
+                    ....\n....\n#...", err.to_string());
+    }
+}
@@ -0,0 +1,104 @@
+use anyhow::{Error, format_err};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/* Options for a solver, given on the command line as `-o KEY=VALUE`. A bare
+ * `-o KEY` means KEY=true. Solvers look up the options they understand; any
+ * left unread afterwards are reported as unknown. */
+#[derive(Default)]
+pub struct Options {
+    values: BTreeMap<String, String>,
+    read: RefCell<BTreeSet<String>>,
+}
+
+#[allow(dead_code)]
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Self, Error> {
+        let mut options = Self::default();
+        for arg in args {
+            let (key, value) = match arg.find('=') {
+                Some(pos) => (&arg[..pos], &arg[pos+1..]),
+                None => (arg.as_str(), "true"),
+            };
+            if key.is_empty() {
+                return Err(format_err!("Bad option: '{}'", arg));
+            }
+            options.values.insert(key.to_string(), value.to_string());
+        }
+        Ok(options)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.read.borrow_mut().insert(key.to_string());
+        self.values.get(key).map(|v| v.as_str())
+    }
+
+    // The option's value converted to T, if it's given.
+    pub fn get_as<T>(&self, key: &str) -> Result<Option<T>, Error>
+        where T: FromStr, T::Err: Display {
+        match self.get(key) {
+            Some(value) => value.parse::<T>().map(Some).map_err(|e| {
+                format_err!("Bad value for option {}: '{}' ({})",
+                            key, value, e)
+            }),
+            None => Ok(None),
+        }
+    }
+
+    // Same, with a default for when it's not given.
+    pub fn get_or<T>(&self, key: &str, default: T) -> Result<T, Error>
+        where T: FromStr, T::Err: Display {
+        Ok(self.get_as(key)?.unwrap_or(default))
+    }
+
+    pub fn flag(&self, key: &str) -> Result<bool, Error> {
+        self.get_or(key, false)
+    }
+
+    // Set an option whatever was given, e.g. to turn something off that a
+    // caller can't support. It isn't reported as unread.
+    pub fn force(&mut self, key: &str, value: &str) {
+        self.values.insert(key.to_string(), value.to_string());
+        self.read.borrow_mut().insert(key.to_string());
+    }
+
+    // Options given that nobody has asked for.
+    pub fn unread(&self) -> Vec<&str> {
+        let read = self.read.borrow();
+        self.values.keys()
+                   .filter(|k| !read.contains(k.as_str()))
+                   .map(|k| k.as_str())
+                   .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Options;
+
+    #[test]
+    fn parse_and_read_options() {
+        let args = vec!["save=game.txt".to_string(),
+                        "fps=30".to_string(),
+                        "headless".to_string(),
+                        "typo=1".to_string()];
+        let options = Options::parse(&args).unwrap();
+        assert_eq!(Some("game.txt"), options.get("save"));
+        assert_eq!(30, options.get_or("fps", 60).unwrap());
+        assert_eq!(5, options.get_or("moves", 5).unwrap());
+        assert_eq!(None, options.get_as::<usize>("moves").unwrap());
+        assert!(options.flag("headless").unwrap());
+        assert!(!options.flag("live").unwrap());
+        assert_eq!(vec!["typo"], options.unread());
+        let mut options = options;
+        options.force("quiet", "true");
+        assert!(options.flag("quiet").unwrap());
+        assert_eq!(vec!["typo"], options.unread());
+        assert_eq!("Bad value for option typo: '1' \
+                    (provided string was not `true` or `false`)",
+                   options.flag("typo").unwrap_err().to_string());
+        assert!(Options::parse(&["=5".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+use super::grid::{Dir, InfiniteGrid, Pos};
+use super::intcode::{Program, Simulator};
+use anyhow::{Error, format_err};
+use std::collections::HashSet;
+
+/* The hull painting robot: its program is given the color of the panel the
+ * robot is on, and answers with the color to paint it and which way to turn
+ * before moving forward one panel. The robot starts on the origin facing up,
+ * and the whole hull starts out black apart from the panel it's on. */
+
+#[derive(Copy,Clone,Debug,Eq,PartialEq)]
+pub enum Color {
+    Black = 0,
+    White = 1,
+}
+
+pub struct Painting {
+    pub hull: InfiniteGrid<Color>,
+    // Panels painted at least once, whatever color they ended up.
+    pub painted: HashSet<Pos>,
+}
+
+#[allow(dead_code)]
+impl Painting {
+    // The painted part of the hull, true where white.
+    pub fn image(&self) -> Vec<Vec<bool>> {
+        self.hull.crop()
+                 .iter()
+                 .map(|row| row.iter().map(|&c| c == Color::White).collect())
+                 .collect()
+    }
+}
+
+// Run the robot program until it halts.
+pub fn paint(program: &Program, start_color: Color)
+    -> Result<Painting, Error> {
+    let mut hull = InfiniteGrid::new(Color::Black);
+    let mut painted = HashSet::new();
+    let mut robot_pos = Pos::origin();
+    let mut robot_dir = Dir::Up;
+    let mut sim = Simulator::with_program(program);
+    let input = sim.create_input_channel();
+    let output = sim.create_output_channel();
+    sim.set_blocking_input(false);
+
+    hull[robot_pos] = start_color;
+
+    loop {
+        input.send(hull[robot_pos] as isize)?;
+        sim.run()?;
+        if !sim.is_running() {
+            break;
+        }
+
+        hull[robot_pos] = match output.recv()? {
+            0 => Color::Black,
+            1 => Color::White,
+            x => return Err(format_err!("Unrecognized color: {}", x)),
+        };
+        painted.insert(robot_pos);
+        match output.recv()? {
+            0 => robot_dir = robot_dir.turn_left(),
+            1 => robot_dir = robot_dir.turn_right(),
+            x => return Err(format_err!("Unrecognized turn: {}", x)),
+        }
+        robot_pos = robot_pos.neighbor(robot_dir);
+    }
+
+    Ok(Painting { hull: hull, painted: painted })
+}
@@ -0,0 +1,94 @@
+use std::time::{Duration, Instant};
+
+/* Times the phases of solving a day: reading the input, part 1 and part 2.
+ * The solver marks the end of parsing and of part 1 as it goes, and part 2 is
+ * whatever comes after. Marks are optional; time in a phase that isn't marked
+ * is counted in the next one (for days where the parts are worked out
+ * together, say). */
+pub struct Stopwatch {
+    start: Instant,
+    parsed: Option<Instant>,
+    part1: Option<Instant>,
+    end: Option<Instant>,
+}
+
+#[derive(Clone,Copy,Debug,Default)]
+pub struct Timings {
+    pub parse: Option<Duration>,
+    pub part1: Option<Duration>,
+    pub part2: Duration,
+    pub total: Duration,
+}
+
+#[allow(dead_code)]
+impl Stopwatch {
+    pub fn new() -> Self {
+        Stopwatch {
+            start: Instant::now(),
+            parsed: None,
+            part1: None,
+            end: None,
+        }
+    }
+
+    // Start over from now.
+    pub fn restart(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn parsed(&mut self) {
+        self.parsed = Some(Instant::now());
+    }
+
+    pub fn part1_done(&mut self) {
+        self.part1 = Some(Instant::now());
+    }
+
+    pub fn stop(&mut self) {
+        self.end = Some(Instant::now());
+    }
+
+    // Times so far, if not stopped yet.
+    pub fn timings(&self) -> Timings {
+        let end = self.end.unwrap_or_else(Instant::now);
+        let part1_start = self.parsed.unwrap_or(self.start);
+        Timings {
+            parse: self.parsed.map(|t| t - self.start),
+            part1: self.part1.map(|t| t - part1_start),
+            part2: end - self.part1.unwrap_or(part1_start),
+            total: end - self.start,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn phases_add_up() {
+        let pause = Duration::from_millis(2);
+        let mut stopwatch = Stopwatch::new();
+        thread::sleep(pause);
+        stopwatch.parsed();
+        thread::sleep(pause);
+        stopwatch.part1_done();
+        thread::sleep(pause);
+        stopwatch.stop();
+        let t = stopwatch.timings();
+        assert!(t.parse.unwrap() >= pause && t.part1.unwrap() >= pause);
+        assert!(t.part2 >= pause);
+        assert_eq!(t.total, t.parse.unwrap() + t.part1.unwrap() + t.part2);
+
+        // Without a part 1 mark, part 2 has both
+        stopwatch.restart();
+        stopwatch.parsed();
+        thread::sleep(pause);
+        stopwatch.stop();
+        let t = stopwatch.timings();
+        assert_eq!(None, t.part1);
+        assert!(t.part2 >= pause);
+        assert_eq!(t.total, t.parse.unwrap() + t.part2);
+    }
+}
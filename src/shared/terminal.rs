@@ -0,0 +1,117 @@
+use anyhow::{Error, format_err};
+use std::io::{self, Read};
+use std::process::{Command, Stdio};
+
+/* Keys read from the terminal one at a time, as they're pressed, for the
+ * interactive modes. The terminal is switched out of line-buffered mode with
+ * stty while a RawTerminal exists, and put back how it was when it's dropped.
+ * Ctrl-C comes through as a key rather than a signal, since being killed
+ * would leave the terminal as it was set up here; callers should stop when
+ * they see it. Output processing is left alone, so newlines work as usual. */
+pub struct RawTerminal {
+    saved: String,
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Escape,
+    // Ctrl-C
+    Interrupt,
+    Char(char),
+}
+
+impl RawTerminal {
+    pub fn enable() -> Result<Self, Error> {
+        let saved = stty(&["-g"])?;
+        // Reads return straight away, with whatever has been typed so far.
+        stty(&["-icanon", "-echo", "-isig", "min", "0", "time", "0"])?;
+        Ok(Self { saved: saved.trim().to_string() })
+    }
+
+    // Keys pressed since the last call, oldest first. Doesn't wait.
+    pub fn keys(&mut self) -> Result<Vec<Key>, Error> {
+        let mut bytes = Vec::new();
+        let mut buffer = [0; 64];
+        loop {
+            let count = io::stdin().read(&mut buffer)?;
+            if count == 0 {
+                break;
+            }
+            bytes.extend_from_slice(&buffer[..count]);
+        }
+        Ok(parse_keys(&bytes))
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> Result<String, Error> {
+    let output = Command::new("stty").args(args)
+                                     .stdin(Stdio::inherit())
+                                     .output()?;
+    if !output.status.success() {
+        return Err(format_err!("stty failed (is stdin a terminal?): {}",
+                               String::from_utf8_lossy(&output.stderr)
+                                      .trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// Arrow keys arrive as escape sequences (ESC [ A to ESC [ D, or ESC O A to
+// ESC O D in application mode); anything else is taken as typed, apart from
+// Ctrl-C.
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let text = String::from_utf8_lossy(bytes);
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x03' {
+            keys.push(Key::Interrupt);
+            continue;
+        }
+        if c != '\x1B' {
+            keys.push(Key::Char(c));
+            continue;
+        }
+        match chars.peek() {
+            Some('[') | Some('O') => { chars.next(); },
+            _ => {
+                keys.push(Key::Escape);
+                continue;
+            }
+        }
+        match chars.next() {
+            Some('A') => keys.push(Key::Up),
+            Some('B') => keys.push(Key::Down),
+            Some('C') => keys.push(Key::Right),
+            Some('D') => keys.push(Key::Left),
+            // Some other sequence, ignored
+            _ => {},
+        }
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_arrows_and_chars() {
+        assert_eq!(vec![Key::Left, Key::Char('a'), Key::Right, Key::Up,
+                        Key::Escape, Key::Char('q')],
+                   parse_keys(b"\x1B[Da\x1BOC\x1B[A\x1Bq"));
+        assert_eq!(vec![Key::Char('d')], parse_keys(b"\x1B[5d"));
+        assert_eq!(vec![Key::Char('a'), Key::Interrupt],
+                   parse_keys(b"a\x03"));
+        assert!(parse_keys(b"").is_empty());
+    }
+}